@@ -1,28 +1,135 @@
-use clap::{Arg, Command};
+use clap::{Arg, ArgMatches, Command};
 use std::error::Error;
 use std::fs;
 use std::io::{Write};
 use std::path::Path;
+use std::process;
 
-mod utils;
-use crate::utils::{read_fasta_sequence};
+use aligner::alignment::{needleman_wunsch, AlignmentResult, Scoring};
+use aligner::formats::{AlignmentInput, AlignmentParameters};
+use aligner::genetic_code::genetic_code;
+use aligner::hgvs::write_hgvs;
+use aligner::json::write_json;
+use aligner::modified_bases::{ModifiedBaseAlphabet, ModifiedBaseCounts};
+use aligner::msa::{
+    progressive_alignment, write_msa_clustal, write_msa_fasta, write_msa_stockholm, MultipleAlignment, TreeMethod,
+};
+use aligner::paf::write_paf;
+use aligner::profile::{align_profiles, align_sequence_to_profile, ColumnScoring, Profile, ProfileAlignment, ProfileScoring};
+use aligner::report::write_needle_report;
+use aligner::sam::write_sam;
+use aligner::score::{parse_score, Score, ScoreOverflow};
+use aligner::utils::{create_output, read_fasta_records, select_fasta_record, STDIO_PATH};
+use aligner::vcf::write_vcf;
 
-// Holds alignment result
-struct AlignmentResult {
-    alignment_score: i32,
-    align1: String,
-    align2: String,
-    alignment_visualization: String,
+// FASTA header line and sequence of an input record
+type HeaderAndSequence = (String, String);
+
+// Errors are printed with their message rather than the Debug form main would use
+fn main() {
+    if let Err(e) = run() {
+        if e.is::<ScoreOverflow>() {
+            eprintln!("Error: {} Use a wider --score-type.", e);
+        } else {
+            eprintln!("Error: {}", e);
+        }
+        process::exit(1);
+    }
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn run() -> Result<(), Box<dyn Error>> {
     let matches = Command::new("Sequence Aligner")
+        .subcommand_negates_reqs(true)
+        .subcommand(
+            Command::new("msa")
+                .about("Progressive multiple sequence alignment of a multi-record FASTA file")
+                .arg(
+                    Arg::new("input")
+                        .short('i')
+                        .long("input")
+                        .value_name("FILE")
+                        .help("Sequences to align, multi-record FASTA format, '-' for stdin")
+                        .required(true)
+                        .value_parser(clap::value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Output multiple alignment file, '-' for stdout")
+                        .required(true)
+                        .value_parser(clap::value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("gap_penalty")
+                        .short('g')
+                        .long("gap")
+                        .value_name("SCORE")
+                        .help("Gap penalty (negative number)")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .value_parser(clap::value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("mismatch_penalty")
+                        .short('p')
+                        .long("mismatch")
+                        .value_name("SCORE")
+                        .help("Mismatch penalty (negative number)")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .value_parser(clap::value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("match_score")
+                        .short('m')
+                        .long("match")
+                        .value_name("SCORE")
+                        .help("Match score (positive number)")
+                        .required(true)
+                        .value_parser(clap::value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("unpenalized_end_gaps")
+                        .short('u')
+                        .long("unpenalized")
+                        .help("Unpenalized start and end gaps")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("tree")
+                        .long("tree")
+                        .value_name("METHOD")
+                        .help("Guide tree: 'upgma' or 'nj' (neighbor joining)")
+                        .default_value("upgma")
+                        .value_parser(["upgma", "nj"]),
+                )
+                .arg(
+                    Arg::new("column_scoring")
+                        .long("column-scoring")
+                        .value_name("METHOD")
+                        .help("Profile column scoring: 'sp' (sum-of-pairs) or 'logodds'")
+                        .default_value("sp")
+                        .value_parser(["sp", "logodds"]),
+                )
+                .arg(
+                    Arg::new("format")
+                        .short('f')
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("Output format: 'fasta' (aligned FASTA), 'clustal' or 'stockholm'")
+                        .default_value("fasta")
+                        .value_parser(["fasta", "clustal", "stockholm"]),
+                )
+                .arg(modified_bases_arg()),
+        )
         .arg(
             Arg::new("query")
                 .short('q')
                 .long("query")
                 .value_name("FILE")
-                .help("Query sequence file in FASTA format")
+                .help("Query sequence file in FASTA format, '-' for stdin")
                 .required(true)
                 .value_parser(clap::value_parser!(String)),
         )
@@ -31,7 +138,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .short('r')
                 .long("reference")
                 .value_name("FILE")
-                .help("Reference sequence file in FASTA format")
+                .help("Reference sequence file in FASTA format, '-' for stdin")
                 .required(true)
                 .value_parser(clap::value_parser!(String)),
         )
@@ -40,7 +147,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .short('o')
                 .long("output")
                 .value_name("FILE")
-                .help("Output alignment file")
+                .help("Output alignment file, '-' for stdout")
                 .required(true)
                 .value_parser(clap::value_parser!(String)),
         )
@@ -48,30 +155,69 @@ fn main() -> Result<(), Box<dyn Error>> {
             Arg::new("gap_penalty")
                 .short('g')
                 .long("gap")
-                .value_name("INT")
-                .help("Gap penalty (negative integer)")
+                .value_name("SCORE")
+                .help("Gap penalty (negative number), fractional with --score-type f64")
                 .required(true)
                 .allow_hyphen_values(true)
-                .value_parser(clap::value_parser!(i32)),
+                .value_parser(clap::value_parser!(String)),
         )
         .arg(
             Arg::new("mismatch_penalty")
                 .short('p')
                 .long("mismatch")
-                .value_name("INT")
-                .help("Mismatch penalty (negative integer)")
+                .value_name("SCORE")
+                .help("Mismatch penalty (negative number), fractional with --score-type f64")
                 .required(true)
                 .allow_hyphen_values(true)
-                .value_parser(clap::value_parser!(i32)),
+                .value_parser(clap::value_parser!(String)),
         )
         .arg(
             Arg::new("match_score")
                 .short('m')
                 .long("match")
-                .value_name("INT")
-                .help("Match score (positive integer)")
+                .value_name("SCORE")
+                .help("Match score (positive number), fractional with --score-type f64")
                 .required(true)
-                .value_parser(clap::value_parser!(i32)),
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("format")
+                .short('f')
+                .long("format")
+                .value_name("FORMAT")
+                .help("Output format: 'text' (default), 'sam', 'bam', 'paf', 'needle' (EMBOSS-style report), 'json', 'fasta' (aligned FASTA), 'clustal', 'stockholm', 'vcf' (variants) or 'hgvs' (variants in HGVS notation)")
+                .default_value("text")
+                .value_parser([
+                    "text", "sam", "bam", "paf", "needle", "json", "fasta", "clustal", "stockholm", "vcf", "hgvs",
+                ]),
+        )
+        .arg(
+            Arg::new("width")
+                .short('w')
+                .long("width")
+                .value_name("COLUMNS")
+                .help("Alignment columns per line in the needle report")
+                .default_value("50")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("genetic_code")
+                .long("genetic-code")
+                .value_name("ID")
+                .help("NCBI genetic code (translation table) used for the protein effects in the hgvs format")
+                .default_value("1")
+                .value_parser(|value: &str| -> Result<u8, String> {
+                    let id = value.parse::<u8>().map_err(|e| e.to_string())?;
+                    genetic_code(id).map(|code| code.id)
+                }),
+        )
+        .arg(
+            Arg::new("score_type")
+                .long("score-type")
+                .value_name("TYPE")
+                .help("Numeric type used for scores: i16, i32, i64 or f64 (for log-odds matrices)")
+                .default_value("i32")
+                .value_parser(["i16", "i32", "i64", "f64"]),
         )
         .arg(
             Arg::new("unpenalized_end_gaps")
@@ -89,75 +235,312 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .required(true)
                 .value_parser(clap::value_parser!(String)),
         )
+        .arg(
+            Arg::new("query_id")
+                .long("query-id")
+                .value_name("ID")
+                .help("Use the record with this id from the query file (default: first record)")
+                .conflicts_with("query_index")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("query_index")
+                .long("query-index")
+                .value_name("N")
+                .help("Use the N-th record (1-based) of the query file")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("reference_id")
+                .long("reference-id")
+                .value_name("ID")
+                .help("Use the record with this id from the reference file (default: first record)")
+                .conflicts_with("reference_index")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("reference_index")
+                .long("reference-index")
+                .value_name("N")
+                .help("Use the N-th record (1-based) of the reference file")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("reference_profile")
+                .long("reference-profile")
+                .help("Treat the reference file as an existing multiple alignment (aligned FASTA) and align against its column profile")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("query_profile")
+                .long("query-profile")
+                .help("Treat the query file as an existing multiple alignment (aligned FASTA)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("column_scoring")
+                .long("column-scoring")
+                .value_name("METHOD")
+                .help("Profile column scoring: 'sp' (sum-of-pairs) or 'logodds'")
+                .default_value("sp")
+                .value_parser(["sp", "logodds"]),
+        )
+        .arg(modified_bases_arg())
         .get_matches();
 
+    if let Some(("msa", msa_matches)) = matches.subcommand() {
+        return run_msa(msa_matches);
+    }
+
+    if matches.get_flag("reference_profile") || matches.get_flag("query_profile") {
+        if matches.get_one::<String>("format").unwrap() != "text" {
+            return Err("Profile alignments are only written in the text format.".into());
+        }
+        return run_profile_alignment(&matches);
+    }
+
+    let score_type = matches.get_one::<String>("score_type").unwrap();
+    eprintln!("Score Type: {}", score_type);
+
+    match score_type.as_str() {
+        "i16" => run_alignment::<i16>(&matches),
+        "i64" => run_alignment::<i64>(&matches),
+        "f64" => run_alignment::<f64>(&matches),
+        _ => run_alignment::<i32>(&matches),
+    }
+}
+
+// --modified-bases, shared by pairwise alignment and msa
+fn modified_bases_arg() -> Arg {
+    Arg::new("modified_bases")
+        .long("modified-bases")
+        .value_name("FILE")
+        .help("Tab-separated 'symbol, canonical base, name' lines of modified bases (e.g. m1Ψ, m5C) to read as their canonical base, in addition to Ψ")
+        .value_parser(clap::value_parser!(String))
+}
+
+// Alphabet from --modified-bases, or the default one (Ψ only)
+fn modified_base_alphabet(matches: &ArgMatches) -> Result<ModifiedBaseAlphabet, Box<dyn Error>> {
+    match matches.get_one::<String>("modified_bases") {
+        Some(file_path) => ModifiedBaseAlphabet::read(file_path),
+        None => Ok(ModifiedBaseAlphabet::default()),
+    }
+}
+
+// Tell the user which modified bases of a record were read as canonical bases
+fn report_modified_bases(alphabet: &ModifiedBaseAlphabet, record_id: &str, counts: &ModifiedBaseCounts) {
+    if !counts.is_empty() {
+        eprintln!("Record '{}': modified bases {}.", record_id, alphabet.describe(counts).join(", "));
+    }
+}
+
+// Parse the scores as type S and run the alignment described by the command line
+fn run_alignment<S: Score>(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
     let query_file = matches.get_one::<String>("query").unwrap();
     let reference_file = matches.get_one::<String>("reference").unwrap();
     let output_file = matches.get_one::<String>("output").unwrap();
-    let gap_penalty: i32 = *matches.get_one::<i32>("gap_penalty").unwrap();
-    let mismatch_penalty: i32 = *matches.get_one::<i32>("mismatch_penalty").unwrap();
-    let match_score: i32 = *matches.get_one::<i32>("match_score").unwrap();
+    let gap_penalty: S = parse_score(matches.get_one::<String>("gap_penalty").unwrap(), "gap penalty")?;
+    let mismatch_penalty: S = parse_score(matches.get_one::<String>("mismatch_penalty").unwrap(), "mismatch penalty")?;
+    let match_score: S = parse_score(matches.get_one::<String>("match_score").unwrap(), "match score")?;
+    let scoring = Scoring {
+        match_score,
+        mismatch_penalty,
+        gap_penalty,
+    };
     let unpenalized_end_gaps = matches.get_flag("unpenalized_end_gaps");
     let sequence_type_input = matches.get_one::<String>("sequence_type").unwrap();
     let sequence_type = sequence_type_input.to_lowercase();
-    println!("Sequence Type: {}", sequence_type);
-    println!("Unpenalized End Gaps: {}", unpenalized_end_gaps);
+    eprintln!("Sequence Type: {}", sequence_type);
+    eprintln!("Unpenalized End Gaps: {}", unpenalized_end_gaps);
+    let alphabet = modified_base_alphabet(matches)?;
 
     check_and_download_file(query_file)?;
     check_and_download_file(reference_file)?;
 
-    if sequence_type == "nucleotide" {
-        let (query_header, query_sequence) = read_fasta_sequence(query_file)?;
-        let (reference_header, reference_sequence) = read_fasta_sequence(reference_file)?;
-    
-        // No translation
-        let alignment = needleman_wunsch(
-            &reference_sequence,
-            &query_sequence,
-            match_score,
-            mismatch_penalty,
-            gap_penalty,
-            unpenalized_end_gaps,
-        );
-    
-        write_alignment_output(output_file, &alignment, &reference_header, &query_header)?;
-    } else if sequence_type == "aminoacid" {
-        let (query_header, query_aa_sequence) = read_fasta_sequence(query_file)?;
-        let (reference_header, reference_aa_sequence) = read_fasta_sequence(reference_file)?;
-    
-        let alignment = needleman_wunsch(
-            &reference_aa_sequence,
-            &query_aa_sequence,
-            match_score,
-            mismatch_penalty,
-            gap_penalty,
-            unpenalized_end_gaps,
-        );
-        
-        write_alignment_output(output_file, &alignment, &reference_header, &query_header)?;
-    } else {
+    // Nucleotide and amino acid sequences are aligned the same way; the type only matters
+    // for the output formats
+    if sequence_type != "nucleotide" && sequence_type != "aminoacid" {
         return Err("Invalid sequence type: specify 'nucleotide' or 'aminoacid'.".into());
     }
+    let ((query_header, query_sequence), (reference_header, reference_sequence)) =
+        read_query_and_reference(matches, query_file, reference_file, &alphabet)?;
+
+    let alignment = needleman_wunsch(&reference_sequence, &query_sequence, &scoring, unpenalized_end_gaps)?;
+
+    let input = AlignmentInput {
+        reference_header: &reference_header,
+        reference_sequence: &reference_sequence,
+        query_header: &query_header,
+        query_sequence: &query_sequence,
+    };
+    let parameters = AlignmentParameters {
+        match_score: match_score.to_f64(),
+        mismatch_penalty: mismatch_penalty.to_f64(),
+        gap_penalty: gap_penalty.to_f64(),
+        unpenalized_end_gaps,
+        sequence_type: &sequence_type,
+        score_type: S::NAME,
+    };
+    write_formatted_output(matches, output_file, &alignment, &input, &parameters)?;
+
+    Ok(())
+}
+
+// Align a sequence or profile against a profile. Column scores are fractional, so scores are f64.
+fn run_profile_alignment(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let query_file = matches.get_one::<String>("query").unwrap();
+    let reference_file = matches.get_one::<String>("reference").unwrap();
+    let output_file = matches.get_one::<String>("output").unwrap();
+    let unpenalized_end_gaps = matches.get_flag("unpenalized_end_gaps");
+    let scoring = parse_profile_scoring(matches)?;
+    eprintln!("Column Scoring: {:?}", scoring.column_scoring);
+    eprintln!("Unpenalized End Gaps: {}", unpenalized_end_gaps);
+    let alphabet = modified_base_alphabet(matches)?;
 
+    check_and_download_file(query_file)?;
+    check_and_download_file(reference_file)?;
 
+    let reference_profile = if matches.get_flag("reference_profile") {
+        Profile::from_alignment(&read_fasta_records(reference_file, &alphabet)?)?
+    } else {
+        let (reference_header, reference_sequence) = select_record(matches, reference_file, "reference", &alphabet)?;
+        Profile::from_sequence(&reference_header, &reference_sequence)
+    };
+    let alignment = if matches.get_flag("query_profile") {
+        let query_profile = Profile::from_alignment(&read_fasta_records(query_file, &alphabet)?)?;
+        eprintln!(
+            "Aligning profile of {} sequences against profile of {} sequences.",
+            query_profile.rows.len(),
+            reference_profile.rows.len()
+        );
+        align_profiles(&reference_profile, &query_profile, &scoring, unpenalized_end_gaps)?
+    } else {
+        let (query_header, query_sequence) = select_record(matches, query_file, "query", &alphabet)?;
+        eprintln!(
+            "Aligning sequence against profile of {} sequences.",
+            reference_profile.rows.len()
+        );
+        align_sequence_to_profile(&reference_profile, &query_header, &query_sequence, &scoring, unpenalized_end_gaps)?
+    };
+    write_profile_output(output_file, &alignment)?;
     Ok(())
 }
 
+// Progressive multiple alignment of all records of one FASTA file
+fn run_msa(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let input_file = matches.get_one::<String>("input").unwrap();
+    let output_file = matches.get_one::<String>("output").unwrap();
+    let unpenalized_end_gaps = matches.get_flag("unpenalized_end_gaps");
+    let scoring = parse_profile_scoring(matches)?;
+    let tree_method = match matches.get_one::<String>("tree").unwrap().as_str() {
+        "nj" => TreeMethod::NeighborJoining,
+        _ => TreeMethod::Upgma,
+    };
+    eprintln!("Guide Tree: {:?}", tree_method);
+    eprintln!("Column Scoring: {:?}", scoring.column_scoring);
+    eprintln!("Unpenalized End Gaps: {}", unpenalized_end_gaps);
+
+    let alphabet = modified_base_alphabet(matches)?;
+
+    check_and_download_file(input_file)?;
+    let records = read_fasta_records(input_file, &alphabet)?;
+    eprintln!("Read {} sequences from '{}'.", records.len(), input_file);
+    for record in &records {
+        report_modified_bases(&alphabet, &record.id, &record.modified_bases);
+    }
+
+    if records.len() >= 2 {
+        eprintln!(
+            "Computing {} pairwise distances, then aligning progressively along the guide tree...",
+            records.len() * (records.len() - 1) / 2
+        );
+    }
+    let alignment = progressive_alignment(&records, &scoring, tree_method, unpenalized_end_gaps)?;
+    match matches.get_one::<String>("format").unwrap().as_str() {
+        "clustal" => write_msa_clustal(output_file, &alignment)?,
+        "stockholm" => write_msa_stockholm(output_file, &alignment)?,
+        _ => write_msa_fasta(output_file, &alignment)?,
+    }
+    Ok(())
+}
+
+// Scores and column scoring shared by profile alignment and msa, always f64
+fn parse_profile_scoring(matches: &ArgMatches) -> Result<ProfileScoring, Box<dyn Error>> {
+    Ok(ProfileScoring {
+        match_score: parse_score(matches.get_one::<String>("match_score").unwrap(), "match score")?,
+        mismatch_penalty: parse_score(matches.get_one::<String>("mismatch_penalty").unwrap(), "mismatch penalty")?,
+        gap_penalty: parse_score(matches.get_one::<String>("gap_penalty").unwrap(), "gap penalty")?,
+        column_scoring: match matches.get_one::<String>("column_scoring").unwrap().as_str() {
+            "logodds" => ColumnScoring::LogOdds,
+            _ => ColumnScoring::SumOfPairs,
+        },
+    })
+}
+
+// Read the query and reference records. When both are '-', stdin holds a two-record stream:
+// the query first, then the reference.
+fn read_query_and_reference(
+    matches: &ArgMatches,
+    query_file: &str,
+    reference_file: &str,
+    alphabet: &ModifiedBaseAlphabet,
+) -> Result<(HeaderAndSequence, HeaderAndSequence), Box<dyn Error>> {
+    if query_file == STDIO_PATH && reference_file == STDIO_PATH {
+        let mut records = read_fasta_records(STDIO_PATH, alphabet)?;
+        if records.len() != 2 {
+            return Err(format!(
+                "Expected two records (query, then reference) on stdin, found {}.",
+                records.len()
+            )
+            .into());
+        }
+        let reference = records.pop().unwrap();
+        let query = records.pop().unwrap();
+        eprintln!("Using records '{}' (query) and '{}' (reference) from stdin.", query.id, reference.id);
+        report_modified_bases(alphabet, &query.id, &query.modified_bases);
+        report_modified_bases(alphabet, &reference.id, &reference.modified_bases);
+        return Ok(((query.header(), query.sequence), (reference.header(), reference.sequence)));
+    }
+    Ok((
+        select_record(matches, query_file, "query", alphabet)?,
+        select_record(matches, reference_file, "reference", alphabet)?,
+    ))
+}
+
+// Record chosen with --<prefix>-id or --<prefix>-index (first record by default) as (header, sequence)
+fn select_record(
+    matches: &ArgMatches,
+    file_path: &str,
+    prefix: &str,
+    alphabet: &ModifiedBaseAlphabet,
+) -> Result<HeaderAndSequence, Box<dyn Error>> {
+    let id = matches.get_one::<String>(&format!("{}_id", prefix)).map(|id| id.as_str());
+    let index = matches.get_one::<usize>(&format!("{}_index", prefix)).copied();
+    let record = select_fasta_record(file_path, id, index, alphabet)?;
+    eprintln!("Using record '{}' from '{}'.", record.id, file_path);
+    report_modified_bases(alphabet, &record.id, &record.modified_bases);
+    Ok((record.header(), record.sequence))
+}
+
 // Download if file nonexistent
 fn check_and_download_file(file_path: &str) -> Result<(), Box<dyn Error>> {
+    if file_path == STDIO_PATH {
+        return Ok(());
+    }
     if Path::new(file_path).exists() {
-        println!("File '{}' already exists.", file_path);
+        eprintln!("File '{}' already exists.", file_path);
     } else {
-        println!("File '{}' not found. Downloading...", file_path);
+        eprintln!("File '{}' not found. Downloading...", file_path);
         let base_url = "https://raw.githubusercontent.com/ScottSauers/Needleman-Wunsch-Aligner/main/";
         let url = format!("{}{}", base_url, file_path);
 
         let response = reqwest::blocking::get(&url)?;
 
         if response.status().is_success() {
-            let content = response.text()?;
+            // Raw bytes, so compressed (.gz) files survive the download
+            let content = response.bytes()?;
             fs::write(file_path, content)?;
-            println!("File '{}' downloaded successfully.", file_path);
+            eprintln!("File '{}' downloaded successfully.", file_path);
         } else {
             return Err(format!(
                 "Failed to download file '{}'. HTTP Status: {}",
@@ -171,180 +554,93 @@ fn check_and_download_file(file_path: &str) -> Result<(), Box<dyn Error>> {
 }
 
 
-// Needleman-Wunsch algorithm, global and semi-global alignment
-// https://bio.libretexts.org/Bookshelves/Computational_Biology/Book%3A_Computational_Biology_-_Genomes_Networks_and_Evolution_(Kellis_et_al.)/03%3A_Rapid_Sequence_Alignment_and_Database_Search/3.03%3A_Global_alignment_vs._Local_alignment_vs._Semi-global_alignment
-// https://ocw.mit.edu/courses/6-096-algorithms-for-computational-biology-spring-2005/01f55f348ea1e95f7015bd1b40586012_lecture5.pdf
-fn needleman_wunsch(
-    seq1: &str,
-    seq2: &str,
-    match_score: i32,
-    mismatch_penalty: i32,
-    gap_penalty: i32,
-    unpenalized_end_gaps: bool,
-) -> AlignmentResult {
-    let m = seq1.len();
-    let n = seq2.len();
-
-    // Init score and traceback matrices
-    // The first row and column are initialized based on whether end gaps are penalized
-    let mut score_matrix = vec![vec![0; n + 1]; m + 1];
-    let mut trace_matrix = vec![vec![' '; n + 1]; m + 1];
-
-    // Init first row and column
-    for i in 0..=m {
-        score_matrix[i][0] = if unpenalized_end_gaps { 0 } else { i as i32 * gap_penalty };
-        trace_matrix[i][0] = 'U'; // Up
-    }
-    for j in 0..=n {
-        score_matrix[0][j] = if unpenalized_end_gaps { 0 } else { j as i32 * gap_penalty };
-        trace_matrix[0][j] = 'L'; // Left
-    }
-    trace_matrix[0][0] = '0';
-
-    // Fill score and traceback matrices
-    for i in 1..=m {
-        let c1 = seq1.chars().nth(i - 1).unwrap(); // Get the char from the first seq
-        for j in 1..=n {
-            let c2 = seq2.chars().nth(j - 1).unwrap(); // Get the char from the 2nd seq
-            // Score for match or mismatch
-            let match_mismatch = if c1 == c2 { match_score } else { mismatch_penalty };
-            
-            // Diagonal score, adding match/mismatch score to the diagonal element
-            let diag_score = score_matrix[i - 1][j - 1] + match_mismatch;
-            
-            // Calculate score for moving up (gap in seq 2), penalize unless unpenalized end gap (which makes # of gaps potentially different from gaps with penalty)
-            let up_score = score_matrix[i - 1][j] + if unpenalized_end_gaps && (i == m) { 0 } else { gap_penalty };
-            
-            // Calculate score for moving left (gap in seq 1), penalize unless unpenalized end gap
-            let left_score = score_matrix[i][j - 1] + if unpenalized_end_gaps && (j == n) { 0 } else { gap_penalty };
-            
-            // Select the max score among diagonal, up, and left moves
-            let max_score = diag_score.max(up_score).max(left_score);
-            score_matrix[i][j] = max_score;
-            
-            // Set trace matrix with D for diagonal, U for up, and L for left
-            if max_score == diag_score {
-                trace_matrix[i][j] = 'D'; // Diagonal
-            } else if max_score == up_score {
-                trace_matrix[i][j] = 'U'; // Up
-            } else {
-                trace_matrix[i][j] = 'L'; // Left
-            }
-        }
-    }
-
-    // Determine the traceback starting point
-    let (max_score, (start_i, start_j)) = if unpenalized_end_gaps {
-        // Semi-global alignment to find the maximum score in the last row and column
-        find_max_in_last_row_and_column(&score_matrix, m, n)
-    } else {
-        // Global alignment using the bottom-right corner
-        (score_matrix[m][n], (m, n))
-    };
-
-    // Traceback to get alignment
-    let mut align1 = String::new();
-    let mut align2 = String::new();
-    let mut alignment_visualization = String::new();
-    let mut i = start_i;
-    let mut j = start_j;
-
-    while i > 0 || j > 0 {
-        // Diagonally move if characters match or mismatch
-        if i > 0 && j > 0 && trace_matrix[i][j] == 'D' {
-            let c1 = seq1.chars().nth(i - 1).unwrap(); // Get character from seq1
-            let c2 = seq2.chars().nth(j - 1).unwrap(); // Get character from seq2
-            align1.push(c1); // Add character from seq1 to align1
-            align2.push(c2);
-            // Match symbol '|' if the characters are the same, else add mismatch 'x'
-            if c1 == c2 {
-                alignment_visualization.push('|');
-            } else {
-                alignment_visualization.push('x');
+// Write the alignment in the format selected with --format
+fn write_formatted_output<S: Score>(
+    matches: &ArgMatches,
+    output_file: &str,
+    alignment: &AlignmentResult<S>,
+    input: &AlignmentInput,
+    parameters: &AlignmentParameters,
+) -> Result<(), Box<dyn Error>> {
+    let format = matches.get_one::<String>("format").unwrap();
+    eprintln!("Output Format: {}", format);
+    match format.as_str() {
+        "sam" | "bam" => write_sam(
+            output_file,
+            input,
+            &alignment.align1,
+            &alignment.align2,
+            alignment.alignment_score.to_f64(),
+            format == "bam",
+        ),
+        "paf" => write_paf(
+            output_file,
+            input,
+            &alignment.align1,
+            &alignment.align2,
+            alignment.alignment_score.to_f64(),
+        ),
+        "needle" => write_needle_report(
+            output_file,
+            input,
+            parameters,
+            &alignment.align1,
+            &alignment.align2,
+            &alignment.alignment_score.to_string(),
+            *matches.get_one::<usize>("width").unwrap(),
+        ),
+        "json" => write_json(
+            output_file,
+            input,
+            parameters,
+            &alignment.align1,
+            &alignment.alignment_visualization,
+            &alignment.align2,
+            &alignment.alignment_score.to_string(),
+        ),
+        "vcf" => write_vcf(output_file, input, &alignment.align1, &alignment.align2),
+        "hgvs" => write_hgvs(
+            output_file,
+            input,
+            parameters.sequence_type,
+            genetic_code(*matches.get_one::<u8>("genetic_code").unwrap())?,
+            &alignment.align1,
+            &alignment.align2,
+        ),
+        "fasta" | "clustal" | "stockholm" => {
+            let pairwise = MultipleAlignment::from_pairwise(
+                input.reference_header,
+                &alignment.align1,
+                input.query_header,
+                &alignment.align2,
+            );
+            match format.as_str() {
+                "clustal" => write_msa_clustal(output_file, &pairwise),
+                "stockholm" => write_msa_stockholm(output_file, &pairwise),
+                _ => write_msa_fasta(output_file, &pairwise),
             }
-            // Move to previous diagonal position
-            i -= 1;
-            j -= 1;
-        }
-        // Move up if there's a gap in seq2
-        else if i > 0 && trace_matrix[i][j] == 'U' {
-            let c1 = seq1.chars().nth(i - 1).unwrap(); // Get the character from seq1
-            align1.push(c1);
-            align2.push('_'); // Add a gap '_' to align2
-            alignment_visualization.push(' '); // Space for gap
-            i -= 1; // Move up to previous row
-        }
-        // Move left if there's a gap in seq1
-        else if j > 0 && trace_matrix[i][j] == 'L' {
-            let c2 = seq2.chars().nth(j - 1).unwrap();
-            align1.push('_');
-            align2.push(c2);
-            alignment_visualization.push(' ');
-            j -= 1; // Move left to previous column
-        }
-        // Exit
-        else {
-            break;
-        }
-    }
-
-    // Reverse the alignments for correct ordering
-    align1 = align1.chars().rev().collect();
-    align2 = align2.chars().rev().collect();
-    alignment_visualization = alignment_visualization.chars().rev().collect();
-
-    // Final alignment score
-    let alignment_score = max_score;
-
-    AlignmentResult {
-        alignment_score,
-        align1,
-        align2,
-        alignment_visualization,
-    }
-}
-
-// Helper function to find the max score in the last row and last column
-fn find_max_in_last_row_and_column(
-    score_matrix: &Vec<Vec<i32>>,
-    m: usize,
-    n: usize,
-) -> (i32, (usize, usize)) {
-    let mut max_score = score_matrix[m][n];
-    let mut max_pos = (m, n);
-
-    // Check last row
-    for j in 0..=n {
-        if score_matrix[m][j] > max_score {
-            max_score = score_matrix[m][j];
-            max_pos = (m, j);
-        }
-    }
-
-    // Check last column
-    for i in 0..=m {
-        if score_matrix[i][n] > max_score {
-            max_score = score_matrix[i][n];
-            max_pos = (i, n);
         }
+        _ => write_alignment_output(output_file, alignment, input.reference_header, input.query_header),
     }
-
-    (max_score, max_pos)
 }
 
 // Write alignment output to file
-fn write_alignment_output(
+fn write_alignment_output<S: Score>(
     output_file: &str,
-    alignment: &AlignmentResult,
+    alignment: &AlignmentResult<S>,
     reference_header: &str,
     query_header: &str,
 ) -> Result<(), Box<dyn Error>> {
-    let mut file = fs::File::create(output_file)?;
-    writeln!(file, "{}", alignment.alignment_score)?;
-    writeln!(file, "{}", reference_header)?;
-    writeln!(file, "{}", alignment.align1.replace(' ', "_"))?;
-    writeln!(file, "{}", alignment.alignment_visualization)?;
-    writeln!(file, "{}", alignment.align2.replace(' ', "_"))?;
-    writeln!(file, "{}", query_header)?;
+    alignment.to_alignment_file(reference_header, query_header).write(output_file)
+}
+
+// Write profile alignment output: the score, then the merged alignment as aligned FASTA
+fn write_profile_output(output_file: &str, alignment: &ProfileAlignment) -> Result<(), Box<dyn Error>> {
+    let mut file = create_output(output_file)?;
+    writeln!(file, "{}", alignment.score)?;
+    for (header, row) in alignment.profile.headers.iter().zip(alignment.profile.aligned_rows()) {
+        writeln!(file, "{}", header)?;
+        writeln!(file, "{}", row)?;
+    }
     Ok(())
 }
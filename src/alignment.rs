@@ -0,0 +1,109 @@
+// Pairwise Needleman-Wunsch alignment of two sequences with a constant gap penalty
+use serde::Deserialize;
+
+use crate::alignment_file::AlignmentFile;
+use crate::dp::{align_dp, AlignOp};
+use crate::score::{Score, ScoreOverflow};
+
+// Scores of a pairwise alignment. Read from pipeline files with the keys of the aligner's
+// options: `gap`, `mismatch` and `match`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scoring<S: Score> {
+    #[serde(rename = "match")]
+    pub match_score: S,
+    #[serde(rename = "mismatch")]
+    pub mismatch_penalty: S,
+    #[serde(rename = "gap")]
+    pub gap_penalty: S,
+}
+
+// Holds alignment result
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlignmentResult<S: Score> {
+    pub alignment_score: S,
+    // Reference (seq1) and query (seq2) with '_' for gaps
+    pub align1: String,
+    pub align2: String,
+    pub alignment_visualization: String,
+}
+
+impl<S: Score> AlignmentResult<S> {
+    // The aligner's text output for this alignment
+    pub fn to_alignment_file(&self, reference_header: &str, query_header: &str) -> AlignmentFile {
+        AlignmentFile {
+            score: self.alignment_score.to_f64(),
+            reference_header: reference_header.to_string(),
+            reference_aligned: self.align1.replace(' ', "_"),
+            markup: self.alignment_visualization.clone(),
+            query_aligned: self.align2.replace(' ', "_"),
+            query_header: query_header.to_string(),
+        }
+    }
+}
+
+// Needleman-Wunsch algorithm, global and semi-global alignment
+// https://bio.libretexts.org/Bookshelves/Computational_Biology/Book%3A_Computational_Biology_-_Genomes_Networks_and_Evolution_(Kellis_et_al.)/03%3A_Rapid_Sequence_Alignment_and_Database_Search/3.03%3A_Global_alignment_vs._Local_alignment_vs._Semi-global_alignment
+// https://ocw.mit.edu/courses/6-096-algorithms-for-computational-biology-spring-2005/01f55f348ea1e95f7015bd1b40586012_lecture5.pdf
+pub fn needleman_wunsch<S: Score>(
+    seq1: &str,
+    seq2: &str,
+    scoring: &Scoring<S>,
+    unpenalized_end_gaps: bool,
+) -> Result<AlignmentResult<S>, ScoreOverflow> {
+    // Index characters directly instead of walking the strings with nth()
+    let seq1: Vec<char> = seq1.chars().collect();
+    let seq2: Vec<char> = seq2.chars().collect();
+
+    // Score for match or mismatch, constant gap penalty in either sequence
+    let (alignment_score, path) = align_dp(
+        seq1.len(),
+        seq2.len(),
+        |i, j| if seq1[i] == seq2[j] { scoring.match_score } else { scoring.mismatch_penalty },
+        |_| scoring.gap_penalty,
+        |_| scoring.gap_penalty,
+        unpenalized_end_gaps,
+    )?;
+
+    // Build the alignment strings from the path
+    let mut align1 = String::new();
+    let mut align2 = String::new();
+    let mut alignment_visualization = String::new();
+    let mut i = 0;
+    let mut j = 0;
+
+    for op in path {
+        match op {
+            // Diagonally move if characters match or mismatch
+            AlignOp::Diagonal => {
+                align1.push(seq1[i]);
+                align2.push(seq2[j]);
+                // Match symbol '|' if the characters are the same, else add mismatch 'x'
+                alignment_visualization.push(if seq1[i] == seq2[j] { '|' } else { 'x' });
+                i += 1;
+                j += 1;
+            }
+            // Gap '_' in seq2
+            AlignOp::Up => {
+                align1.push(seq1[i]);
+                align2.push('_');
+                alignment_visualization.push(' '); // Space for gap
+                i += 1;
+            }
+            // Gap '_' in seq1
+            AlignOp::Left => {
+                align1.push('_');
+                align2.push(seq2[j]);
+                alignment_visualization.push(' ');
+                j += 1;
+            }
+        }
+    }
+
+    Ok(AlignmentResult {
+        alignment_score,
+        align1,
+        align2,
+        alignment_visualization,
+    })
+}
@@ -0,0 +1,181 @@
+// Reader and writer for the aligner's six-line text output:
+// score, reference header, aligned reference, markup, aligned query, query header
+use std::error::Error;
+use std::fmt;
+use std::io::{BufRead, Write};
+
+use crate::utils::{create_output, open_maybe_gzipped};
+
+// Gap character in the aligned sequence lines
+pub const TEXT_GAP: char = '_';
+
+// Markup characters: '|' match, 'x' mismatch, ' ' gap
+pub const MARKUP_MATCH: char = '|';
+pub const MARKUP_MISMATCH: char = 'x';
+pub const MARKUP_GAP: char = ' ';
+
+// Contents of an alignment text file. The score is kept as f64, which holds every
+// score type the aligner writes; integer scores are written back without a fraction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlignmentFile {
+    pub score: f64,
+    pub reference_header: String,
+    pub reference_aligned: String,
+    pub markup: String,
+    pub query_aligned: String,
+    pub query_header: String,
+}
+
+// Problem found while reading an alignment file; `line` is 1-based
+#[derive(Debug)]
+pub struct AlignmentFileError {
+    pub source_name: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AlignmentFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, line {}: {}", self.source_name, self.line, self.message)
+    }
+}
+
+impl Error for AlignmentFileError {}
+
+// One alignment column where the sequences differ; `column` is 1-based
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnDifference {
+    pub column: usize,
+    pub reference: char,
+    pub query: char,
+}
+
+impl AlignmentFile {
+    // Read and validate an alignment file ('-' for stdin, gzip accepted)
+    pub fn read(path: &str) -> Result<AlignmentFile, Box<dyn Error>> {
+        AlignmentFile::parse(open_maybe_gzipped(path)?, path)
+    }
+
+    // Parse an alignment from a reader; `source_name` is used in error messages
+    pub fn parse<R: BufRead>(reader: R, source_name: &str) -> Result<AlignmentFile, Box<dyn Error>> {
+        let error = |line: usize, message: String| AlignmentFileError {
+            source_name: source_name.to_string(),
+            line,
+            message,
+        };
+
+        let mut lines: Vec<String> = reader.lines().collect::<Result<_, _>>()?;
+        for line in lines.iter_mut() {
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        // Trailing blank lines are allowed, nothing else after the query header
+        while lines.len() > 6 && lines.last().is_some_and(|line| line.trim().is_empty()) {
+            lines.pop();
+        }
+        if lines.len() < 6 {
+            return Err(error(
+                lines.len() + 1,
+                format!("file ends early, expected 6 lines (score, headers and alignment), found {}", lines.len()),
+            )
+            .into());
+        }
+        if lines.len() > 6 {
+            return Err(error(7, "unexpected content after the query header".to_string()).into());
+        }
+
+        let score: f64 = lines[0]
+            .trim()
+            .parse()
+            .map_err(|_| error(1, format!("alignment score '{}' is not a number", lines[0].trim())))?;
+        if !score.is_finite() {
+            return Err(error(1, format!("alignment score '{}' is not finite", lines[0].trim())).into());
+        }
+        for line in [2, 6] {
+            if !lines[line - 1].starts_with('>') {
+                return Err(error(line, "expected a FASTA header line starting with '>'".to_string()).into());
+            }
+        }
+
+        let reference: Vec<char> = lines[2].chars().collect();
+        let markup: Vec<char> = lines[3].chars().collect();
+        let query: Vec<char> = lines[4].chars().collect();
+        if markup.len() != reference.len() || query.len() != reference.len() {
+            return Err(error(
+                4,
+                format!(
+                    "aligned reference, markup and aligned query differ in length ({}, {} and {} columns)",
+                    reference.len(),
+                    markup.len(),
+                    query.len()
+                ),
+            )
+            .into());
+        }
+        for (column, ((r, m), q)) in reference.iter().zip(&markup).zip(&query).enumerate() {
+            let expected = if *r == TEXT_GAP || *q == TEXT_GAP {
+                MARKUP_GAP
+            } else if r == q {
+                MARKUP_MATCH
+            } else {
+                MARKUP_MISMATCH
+            };
+            if *m != expected {
+                return Err(error(
+                    4,
+                    format!("column {}: markup '{}' does not fit '{}' over '{}', expected '{}'", column + 1, m, r, q, expected),
+                )
+                .into());
+            }
+        }
+
+        let mut lines = lines.into_iter();
+        let mut next = || lines.next().unwrap();
+        next();
+        Ok(AlignmentFile {
+            score,
+            reference_header: next(),
+            reference_aligned: next(),
+            markup: next(),
+            query_aligned: next(),
+            query_header: next(),
+        })
+    }
+
+    // Write the alignment in the format `read` accepts ('-' for stdout)
+    pub fn write(&self, output_file: &str) -> Result<(), Box<dyn Error>> {
+        let mut file = create_output(output_file)?;
+        writeln!(file, "{}", self.score)?;
+        writeln!(file, "{}", self.reference_header)?;
+        writeln!(file, "{}", self.reference_aligned)?;
+        writeln!(file, "{}", self.markup)?;
+        writeln!(file, "{}", self.query_aligned)?;
+        writeln!(file, "{}", self.query_header)?;
+        Ok(())
+    }
+
+    // Match, mismatch and gap column counts
+    pub fn counts(&self) -> (usize, usize, usize) {
+        let matches = self.markup.matches(MARKUP_MATCH).count();
+        let mismatches = self.markup.matches(MARKUP_MISMATCH).count();
+        let gaps = self.markup.matches(MARKUP_GAP).count();
+        (matches, mismatches, gaps)
+    }
+
+    // Mismatch and gap columns, in alignment order
+    pub fn differences(&self) -> Vec<ColumnDifference> {
+        self.reference_aligned
+            .chars()
+            .zip(self.query_aligned.chars())
+            .zip(self.markup.chars())
+            .enumerate()
+            .filter(|(_, (_, m))| *m != MARKUP_MATCH)
+            .map(|(i, ((reference, query), _))| ColumnDifference {
+                column: i + 1,
+                reference,
+                query,
+            })
+            .collect()
+    }
+}
@@ -1,251 +1,672 @@
+use clap::{Arg, Command};
 use statrs::distribution::{ContinuousCDF, Normal};
+use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error;
-use std::fs;
-use std::io::{self, BufRead};
-use std::path::Path;
-use std::process::Command;
-
-mod utils;
-use crate::utils::{read_fasta_sequence, translate_sequence, save_sequence_to_file};
-
-const SEQUENCE_TYPE_NUCLEOTIDE: &str = "nucleotide";
-const SEQUENCE_TYPE_AMINOACID: &str = "aminoacid";
+use std::io::Write;
+use std::process;
+
+mod pipeline;
+use aligner::alignment::{needleman_wunsch, Scoring};
+use aligner::alignment_file::AlignmentFile;
+use aligner::codon_usage::{
+    back_translate, codon_adaptation_index, compare_to_optimal, CodonUsage, OptimalCodonComparison,
+};
+use aligner::codons::{classify_codons, codon_alignment, CodonChange, CodonSummary};
+use aligner::dnds::{estimate_dn_ds, ConfidenceInterval, DnDsEstimate};
+use aligner::genetic_code::{genetic_code, GeneticCode};
+use aligner::modified_bases::{ModifiedBaseAlphabet, ModifiedBaseCounts};
+use aligner::orf::{find_orfs, longest_orf, six_frame_translation, Orf, Strand};
+use aligner::utils::{create_output, open_fasta, read_fasta_sequence, save_sequence_to_file};
+use crate::pipeline::{Comparison, Pipeline, Step};
+
+// An input with its CDS
+struct CodingSequence {
+    sequence: String,
+    cds: Orf,
+    amino_acid_file: String,
+}
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let aligner_path = "./target/release/aligner";
+// Everything measured for one comparison; None where an alignment was not run
+struct ComparisonResult {
+    penalized: Option<AlignmentFile>,
+    free: Option<AlignmentFile>,
+    codons: CodonSummary,
+    dn_ds: DnDsEstimate,
+    amino_acid: Option<AlignmentFile>,
+    gc_z_score: f64,
+    gc_p_value: f64,
+}
 
-    if !Path::new(aligner_path).exists() {
-        println!(
-            "Error: Aligner binary not found at '{}'. Build it using 'cargo build --release'.",
-            aligner_path
-        );
-        return Ok(());
+// Errors are printed with their message rather than the Debug form main would use
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        process::exit(1);
     }
+}
 
-    println!("Running alignment with penalties for start/end gaps. Query is pfizer_mrna.fna. Reference is sars_spike_protein.fna. Check output in question2_output.txt. Gap penalty is -2, mismatch penalty is -1, and match score is 1.");
-
-    let alignment2_output = "question2_output.txt";
-    let status = Command::new(&aligner_path)
-        .args(&[
-            "--query=pfizer_mrna.fna",
-            "--reference=sars_spike_protein.fna",
-            "--output=question2_output.txt",
-            "--gap=-2",
-            "--mismatch=-1",
-            "--match=1",
-            "--type", SEQUENCE_TYPE_NUCLEOTIDE,
-        ])
-        .status()?;
-    if status.success() {
-        println!("Alignment with penalties for start/end gaps completed successfully.");
-    } else {
-        println!("Warning: alignment with penalties for start/end gaps failed.");
+fn run() -> Result<(), Box<dyn Error>> {
+    let matches = Command::new("analysis")
+        .about("Runs the alignments and analyses of a pipeline file")
+        .arg(
+            Arg::new("config")
+                .short('c')
+                .long("config")
+                .value_name("FILE")
+                .help("Pipeline file (TOML), the bundled analysis.toml by default")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .get_matches();
+
+    let pipeline = match matches.get_one::<String>("config") {
+        Some(file_path) => Pipeline::read(file_path)?,
+        None => Pipeline::default_pipeline()?,
+    };
+    let code = genetic_code(pipeline.translation.genetic_code)?;
+    let alphabet = match &pipeline.modified_bases {
+        Some(file_path) => ModifiedBaseAlphabet::read(file_path)?,
+        None => ModifiedBaseAlphabet::default(),
+    };
+
+    let mut sequences = BTreeMap::new();
+    let mut results = Vec::new();
+    for step in &pipeline.steps {
+        match step {
+            Step::Orfs => sequences = read_coding_sequences(&pipeline, code, &alphabet)?,
+            Step::Comparisons => {
+                for comparison in &pipeline.comparisons {
+                    println!("=== {} ===", comparison.name);
+                    results.push(run_comparison(&pipeline, comparison, &sequences, code, &alphabet)?);
+                }
+            }
+            Step::ModifiedBases => print_modified_bases(&pipeline, &alphabet)?,
+            Step::CodonOptimality => report_codon_optimality(&pipeline, &sequences, code)?,
+            Step::CodonUsage => report_codon_usage(&pipeline, &sequences, code)?,
+            Step::Summary => print_comparison_table(&pipeline.comparisons, &results),
+        }
     }
 
-    println!("Running alignment with free start/end gaps. Query is pfizer_mrna.fna. Reference is sars_spike_protein.fna. Check output at question3_output.txt. Gap penalty is -2, mismatch penalty is -1, and match score is 1. Flag unpenalized is active.");
-
-    let alignment3_output = "question3_output.txt";
-    let status = Command::new(&aligner_path)
-        .args(&[
-            "--query=pfizer_mrna.fna",
-            "--reference=sars_spike_protein.fna",
-            "--output=question3_output.txt",
-            "--gap=-2",
-            "--mismatch=-1",
-            "--match=1",
-            "--unpenalized",
-            "--type", SEQUENCE_TYPE_NUCLEOTIDE, 
-        ])
-        .status()?;
-    if status.success() {
-        println!("Alignment with free start/end gaps completed successfully.");
+    Ok(())
+}
+
+// Protein of a CDS with one residue per codon, the stop codon included
+fn cds_protein(cds: &Orf) -> String {
+    if cds.has_stop {
+        format!("{}*", cds.protein)
     } else {
-        println!("Warning: alignment with free start/end gaps did not complete successfully.");
+        cds.protein.clone()
     }
+}
 
-    println!("Getting alignment scores...");
-    let score2 = read_alignment_score(alignment2_output)?;
-    let score3 = read_alignment_score(alignment3_output)?;
+// Read every input, find its CDS and save the translated CDS
+fn read_coding_sequences(
+    pipeline: &Pipeline,
+    code: &GeneticCode,
+    alphabet: &ModifiedBaseAlphabet,
+) -> Result<BTreeMap<String, CodingSequence>, Box<dyn Error>> {
+    println!("Reading nucleotide sequences.");
     println!(
-        "Alignment with penalties for start/end gaps score: {}",
-        score2
+        "Finding ORFs of at least {} amino acids in all six frames.",
+        pipeline.translation.min_orf_length
     );
-    println!("Alignment with free start/end gaps score: {}", score3);
-
-    println!("Analyzing Alignment with penalties for start/end gaps...");
-    let (matches2, mismatches2, gaps2) = parse_alignment(alignment2_output)?;
-    let total_mismatches2 = mismatches2 + gaps2;
-    println!("Alignment with penalties for start/end gaps (With penalties for start/end gaps):");
-    println!("Matches: {}", matches2);
-    println!("Mismatches: {}", mismatches2);
-    println!("Gaps, including unpenalized gaps: {}", gaps2);
-    println!("Total Mismatches (including gaps): {}", total_mismatches2);
-
-    println!("Finding alignment with free start/end gaps...");
-    let (matches3, mismatches3, gaps3) = parse_alignment(alignment3_output)?;
-    let total_mismatches3 = mismatches3 + gaps3;
-    println!("Alignment with free start/end gaps:");
-    println!("Matches: {}", matches3);
-    println!("Mismatches: {}", mismatches3);
-    println!("Gaps: {}", gaps3);
-    println!("Total Mismatches (including gaps): {}", total_mismatches3);
+    let mut sequences = BTreeMap::new();
+    for input in &pipeline.inputs {
+        let (header, sequence) = read_fasta_sequence(&input.file, alphabet)?;
+        let cds = find_cds(&input.file, &sequence, pipeline, code)?;
+        save_sequence_to_file(&input.amino_acid_file, &header, &cds.protein)?;
+        sequences.insert(
+            input.file.clone(),
+            CodingSequence {
+                sequence,
+                cds,
+                amino_acid_file: input.amino_acid_file.clone(),
+            },
+        );
+    }
+    Ok(sequences)
+}
 
-    println!("Reading nucleotide sequences.");
-    let (ref_header, ref_sequence) = read_fasta_sequence("sars_spike_protein.fna")?;
-    let (query_header, query_sequence) = read_fasta_sequence("pfizer_mrna.fna")?;
+// Modified bases count as the base they replace in the GC content
+fn print_modified_bases(pipeline: &Pipeline, alphabet: &ModifiedBaseAlphabet) -> Result<(), Box<dyn Error>> {
+    for input in &pipeline.inputs {
+        let counts = count_modified_bases(&input.file, alphabet)?;
+        if counts.is_empty() {
+            println!("{} modified bases: none", input.file);
+        } else {
+            println!("{} modified bases: {}", input.file, alphabet.describe(&counts).join(", "));
+        }
+    }
+    Ok(())
+}
+
+// Back-translation with the most frequent codons of the table, and how often every CDS uses them
+fn report_codon_optimality(
+    pipeline: &Pipeline,
+    sequences: &BTreeMap<String, CodingSequence>,
+    code: &GeneticCode,
+) -> Result<(), Box<dyn Error>> {
+    // Checked when the pipeline is read
+    let step = pipeline.codon_usage.as_ref().unwrap();
+    println!("Comparing codon choices with the most frequent codons of {}.", step.table);
+    let usage = CodonUsage::read(&step.table)?;
+    let optimal_sequence = back_translate(&sequences[&step.back_translate].cds.protein, &usage, code)?;
+    save_sequence_to_file(&step.back_translation_output, &step.back_translation_header, &optimal_sequence)?;
+    println!("Back-translated {} protein saved to {}.", step.back_translate, step.back_translation_output);
+    let comparisons: Vec<(&str, OptimalCodonComparison)> = coding_sequences(pipeline, sequences)
+        .iter()
+        .map(|(name, cds)| (*name, compare_to_optimal(cds, &usage, code)))
+        .collect();
+    print_codon_optimality(&comparisons);
+    write_codon_optimality(&step.optimality_output, &comparisons)?;
+    println!("Per-codon comparison saved to {}.", step.optimality_output);
+    Ok(())
+}
 
-    println!("Translating sequences to amino acids.");
-    let ref_aa_sequence = translate_sequence(&ref_sequence)?;
-    let query_aa_sequence = translate_sequence(&query_sequence)?;
+// CAI of every CDS against the table, then codon counts and RSCU side by side with the table
+fn report_codon_usage(
+    pipeline: &Pipeline,
+    sequences: &BTreeMap<String, CodingSequence>,
+    code: &GeneticCode,
+) -> Result<(), Box<dyn Error>> {
+    // Checked when the pipeline is read
+    let step = pipeline.codon_usage.as_ref().unwrap();
+    let usage = CodonUsage::read(&step.table)?;
+    let coding_sequences = coding_sequences(pipeline, sequences);
+    println!("Codon Adaptation Index against {}:", step.table);
+    for (name, cds) in &coding_sequences {
+        println!("{}: CAI {:.4}", name, codon_adaptation_index(cds, &usage, code));
+    }
+    let usages: Vec<(&str, CodonUsage)> = coding_sequences
+        .iter()
+        .map(|(name, cds)| (*name, CodonUsage::from_coding_sequence(cds)))
+        .chain(std::iter::once((step.table.as_str(), usage)))
+        .collect();
+    print_codon_usage(&usages, code);
+    Ok(())
+}
+
+// Nucleotide CDS of every input, in pipeline order
+fn coding_sequences<'a>(pipeline: &'a Pipeline, sequences: &BTreeMap<String, CodingSequence>) -> Vec<(&'a str, String)> {
+    pipeline
+        .inputs
+        .iter()
+        .map(|input| {
+            let sequence = &sequences[&input.file];
+            (input.file.as_str(), sequence.cds.coding_sequence(&sequence.sequence))
+        })
+        .collect()
+}
+
+// Nucleotide alignments with penalized and free end gaps, codon classification and dN/dS on the
+// latter, the alignment of the translated CDS and a GC content test for one pair of sequences
+fn run_comparison(
+    pipeline: &Pipeline,
+    comparison: &Comparison,
+    sequences: &BTreeMap<String, CodingSequence>,
+    code: &GeneticCode,
+    alphabet: &ModifiedBaseAlphabet,
+) -> Result<ComparisonResult, Box<dyn Error>> {
+    let query = &sequences[&comparison.query];
+    let reference = &sequences[&comparison.reference];
+    let scoring = pipeline.scoring_for(comparison);
+
+    let penalized = match &comparison.penalized_output {
+        Some(output) => {
+            println!("Running alignment with penalties for start/end gaps. Query is {}. Reference is {}. Check output in {}. Gap penalty is {}, mismatch penalty is {}, and match score is {}.", comparison.query, comparison.reference, output, scoring.gap_penalty, scoring.mismatch_penalty, scoring.match_score);
+            let alignment = align_files(
+                &comparison.query,
+                &comparison.reference,
+                output,
+                &scoring,
+                false,
+                alphabet,
+            )?;
+            println!("Alignment with penalties for start/end gaps completed successfully.");
+            Some(alignment)
+        }
+        None => None,
+    };
+
+    let free = match &comparison.free_output {
+        Some(output) => {
+            println!("Running alignment with free start/end gaps. Query is {}. Reference is {}. Check output at {}. Gap penalty is {}, mismatch penalty is {}, and match score is {}. Flag unpenalized is active.", comparison.query, comparison.reference, output, scoring.gap_penalty, scoring.mismatch_penalty, scoring.match_score);
+            let alignment = align_files(
+                &comparison.query,
+                &comparison.reference,
+                output,
+                &scoring,
+                true,
+                alphabet,
+            )?;
+            println!("Alignment with free start/end gaps completed successfully.");
+            Some(alignment)
+        }
+        None => None,
+    };
+
+    if let Some(alignment) = &penalized {
+        println!("Alignment with penalties for start/end gaps score: {}", alignment.score);
+        let (matches, mismatches, gaps) = alignment.counts();
+        println!("Alignment with penalties for start/end gaps:");
+        println!("Matches: {}", matches);
+        println!("Mismatches: {}", mismatches);
+        println!("Gaps, including unpenalized gaps: {}", gaps);
+        println!("Total Mismatches (including gaps): {}", mismatches + gaps);
+    }
 
-    save_sequence_to_file("sars_spike_protein.aa", &ref_header, &ref_aa_sequence)?;
-    save_sequence_to_file("pfizer_mrna.aa", &query_header, &query_aa_sequence)?;
+    if let Some(alignment) = &free {
+        println!("Alignment with free start/end gaps score: {}", alignment.score);
+        let (matches, mismatches, gaps) = alignment.counts();
+        println!("Alignment with free start/end gaps:");
+        println!("Matches: {}", matches);
+        println!("Mismatches: {}", mismatches);
+        println!("Gaps: {}", gaps);
+        println!("Total Mismatches (including gaps): {}", mismatches + gaps);
+    }
 
+    // Codons are phased and numbered from the start of the reference CDS, so UTRs and the
+    // sequence around the ORFs are left out of the codon classes and dN/dS
+    let reference_cds = reference.cds.coding_sequence(&reference.sequence);
+    let query_cds = query.cds.coding_sequence(&query.sequence);
     println!(
-        "Running amino acid alignment... Gap penalty -2, mismatch penalty -1, and match score 1."
+        "Aligning the protein of the CDS of {} ({}..{}) against the protein of the CDS of {} ({}..{}) with free start/end gaps.",
+        comparison.query,
+        query.cds.start,
+        query.cds.end,
+        comparison.reference,
+        reference.cds.start,
+        reference.cds.end
     );
-
-    let alignment_aa_output = "question8_output.txt";
-
-    let status = Command::new(&aligner_path)
-        .args(&[
-            "--query=pfizer_mrna.aa",
-            "--reference=sars_spike_protein.aa",
-            "--output=question8_output.txt",
-            "--gap=-2",
-            "--mismatch=-1",
-            "--match=1",
-            "--type", SEQUENCE_TYPE_AMINOACID,
-        ])
-        .status()?;
-    if status.success() {
-        println!("Amino Acid Alignment completed successfully.");
-    } else {
-        println!("Warning: amino acid alignment failed.");
+    let protein_alignment = needleman_wunsch(&cds_protein(&reference.cds), &cds_protein(&query.cds), &scoring, true)
+        .map_err(|e| format!("Aligning the CDS protein of '{}' against '{}': {}", comparison.query, comparison.reference, e))?
+        .to_alignment_file(&comparison.reference, &comparison.query);
+    // Indels of the protein alignment become whole codons, so no codon is split by a gap
+    let codon_alignment = codon_alignment(&reference_cds, &query_cds, &protein_alignment)
+        .map_err(|e| format!("Codon alignment of '{}' against '{}': {}", comparison.query, comparison.reference, e))?;
+    println!("Classifying codon differences in the codon alignment...");
+    let (codon_differences, codon_summary) = classify_codons(&codon_alignment, code);
+    println!("Identical codons: {}", codon_summary.identical);
+    println!("Synonymous codons: {}", codon_summary.synonymous);
+    println!("Missense codons: {}", codon_summary.missense);
+    println!("Nonsense codons: {}", codon_summary.nonsense);
+    println!("Codons not classified (gaps or ambiguous bases): {}", codon_summary.unclassified);
+    for difference in codon_differences.iter().filter(|d| d.change != CodonChange::Synonymous) {
+        println!(
+            "Codon {}: {} ({}) -> {} ({}), {:?}",
+            difference.index,
+            difference.reference_codon,
+            difference.reference_amino_acid,
+            difference.query_codon,
+            difference.query_amino_acid,
+            difference.change
+        );
     }
 
-    if Path::new(alignment_aa_output).exists() {
-        let (matches_aa, mismatches_aa, gaps_aa) = parse_alignment(alignment_aa_output)?;
-        let total_mismatches_aa = mismatches_aa + gaps_aa;
+    let statistics = &pipeline.statistics;
+    println!(
+        "Estimating dN/dS (Nei-Gojobori, Jukes-Cantor corrected, {} bootstrap replicates)...",
+        statistics.dn_ds_replicates
+    );
+    let estimate = estimate_dn_ds(&codon_alignment, code, statistics.dn_ds_replicates, statistics.dn_ds_seed);
+    println!("Codons used: {}", estimate.codons);
+    println!(
+        "Synonymous sites: {:.2}, differences: {:.2}",
+        estimate.counts.synonymous_sites, estimate.counts.synonymous_differences
+    );
+    println!(
+        "Non-synonymous sites: {:.2}, differences: {:.2}",
+        estimate.counts.nonsynonymous_sites, estimate.counts.nonsynonymous_differences
+    );
+    println!(
+        "pN: {:.4}, pS: {:.4}",
+        estimate.counts.p_nonsynonymous(),
+        estimate.counts.p_synonymous()
+    );
+    println!("dN: {} {}", format_estimate(estimate.dn), format_interval(&estimate.dn_interval));
+    println!("dS: {} {}", format_estimate(estimate.ds), format_interval(&estimate.ds_interval));
+    println!("dN/dS: {} {}", format_estimate(estimate.dn_ds), format_interval(&estimate.dn_ds_interval));
+
+    let amino_acid = match &comparison.amino_acid_output {
+        Some(output) => {
+            println!(
+                "Running amino acid alignment of {} against {}... Gap penalty {}, mismatch penalty {}, and match score {}.",
+                query.amino_acid_file, reference.amino_acid_file, scoring.gap_penalty, scoring.mismatch_penalty, scoring.match_score
+            );
+            let alignment = align_files(
+                &query.amino_acid_file,
+                &reference.amino_acid_file,
+                output,
+                &scoring,
+                false,
+                alphabet,
+            )?;
+            println!("Amino Acid Alignment completed successfully.");
+            Some(alignment)
+        }
+        None => None,
+    };
+    if let Some(alignment_aa) = &amino_acid {
+        let (matches_aa, mismatches_aa, gaps_aa) = alignment_aa.counts();
         println!("Amino acid alignment:");
         println!("Matches: {}", matches_aa);
         println!("Mismatches: {}", mismatches_aa);
         println!("Gaps: {}", gaps_aa);
-        println!("Total mismatches including gaps: {}", total_mismatches_aa);
+        println!("Total mismatches including gaps: {}", mismatches_aa + gaps_aa);
 
         println!("Differences between a.a. sequences:");
-        let differences_aa = extract_differences_aa(alignment_aa_output)?;
+        let differences_aa = alignment_aa.differences();
         if differences_aa.is_empty() {
             println!("No differences found");
         } else {
             for diff in differences_aa {
-                println!("{}", diff);
+                println!("Position {}: {} vs {}", diff.column, diff.reference, diff.query);
             }
         }
-    } else {
-        println!("No amino acid file '{}' found.", alignment_aa_output);
     }
 
-    let (gc_real, gc_real_total, gc_real_count) = calculate_gc_content("sars_spike_protein.fna")?;
-    let (gc_pfizer, gc_pfizer_total, gc_pfizer_count) = calculate_gc_content("pfizer_mrna.fna")?;
+    let (gc_reference, gc_reference_total, gc_reference_count) = calculate_gc_content(&comparison.reference, alphabet)?;
+    let (gc_query, gc_query_total, gc_query_count) = calculate_gc_content(&comparison.query, alphabet)?;
     println!(
-        "sars_spike_protein.fna GC Content: {:.2}% (GC Count: {}, Total: {})",
-        gc_real, gc_real_count, gc_real_total
+        "{} GC Content: {:.2}% (GC Count: {}, Total: {})",
+        comparison.reference, gc_reference, gc_reference_count, gc_reference_total
     );
     println!(
-        "pfizer_mrna.fna GC Content: {:.2}% (GC Count: {}, Total: {})",
-        gc_pfizer, gc_pfizer_count, gc_pfizer_total
+        "{} GC Content: {:.2}% (GC Count: {}, Total: {})",
+        comparison.query, gc_query, gc_query_count, gc_query_total
     );
 
     println!("Performing Z-Test on GC Content...");
 
-    let z_score = perform_z_test(
-        gc_real_count,
-        gc_real_total,
-        gc_pfizer_count,
-        gc_pfizer_total,
-    )?;
+    let z_score = perform_z_test(gc_reference_count, gc_reference_total, gc_query_count, gc_query_total)?;
     println!("Z-Score: {:.4}", z_score);
     let normal_dist = Normal::new(0.0, 1.0)?;
     let p_value = 2.0 * (1.0 - normal_dist.cdf(z_score.abs())).max(0.0);
-    println!(
-        "P-Value: {}",
-        if p_value == 0.0 {
-            "< 1e-10".to_string()
-        } else {
-            format!("{:.4}", p_value)
-        }
-    );
-    if p_value < 0.05 {
+    println!("P-Value: {}", format_p_value(p_value));
+    if p_value < pipeline.statistics.gc_significance {
         println!("Result: Significant difference in GC content.");
     } else {
         println!("Result: No significant difference in GC content.");
     }
 
+    Ok(ComparisonResult {
+        penalized,
+        free,
+        codons: codon_summary,
+        dn_ds: estimate,
+        amino_acid,
+        gc_z_score: z_score,
+        gc_p_value: p_value,
+    })
+}
+
+// Align the first records of two files and write the alignment in the aligner's text format
+fn align_files(
+    query_file: &str,
+    reference_file: &str,
+    output: &str,
+    scoring: &Scoring<i32>,
+    unpenalized: bool,
+    alphabet: &ModifiedBaseAlphabet,
+) -> Result<AlignmentFile, Box<dyn Error>> {
+    let (query_header, query) = read_fasta_sequence(query_file, alphabet)?;
+    let (reference_header, reference) = read_fasta_sequence(reference_file, alphabet)?;
+    let alignment = needleman_wunsch(&reference, &query, scoring, unpenalized)
+        .map_err(|e| format!("Aligning '{}' against '{}': {}", query_file, reference_file, e))?;
+    let alignment_file = alignment.to_alignment_file(&reference_header, &query_header);
+    alignment_file.write(output)?;
+    Ok(alignment_file)
+}
+
+// One row per measure, one column per comparison
+fn print_comparison_table(comparisons: &[Comparison], results: &[ComparisonResult]) {
+    let names: Vec<&str> = comparisons.iter().map(|comparison| comparison.name.as_str()).collect();
+    println!("Comparison summary:");
+    println!("Measure\t{}", names.join("\t"));
+
+    let alignments: [(&str, Vec<Option<&AlignmentFile>>); 3] = [
+        ("penalized end gaps", results.iter().map(|result| result.penalized.as_ref()).collect()),
+        ("free end gaps", results.iter().map(|result| result.free.as_ref()).collect()),
+        ("amino acids", results.iter().map(|result| result.amino_acid.as_ref()).collect()),
+    ];
+    for (label, column) in alignments {
+        let summaries: Vec<[String; 4]> = column.into_iter().map(alignment_summary).collect();
+        for (i, measure) in ["Score", "Matches", "Mismatches", "Gaps"].iter().enumerate() {
+            print_row(
+                &format!("{} ({})", measure, label),
+                summaries.iter().map(|summary| summary[i].clone()).collect(),
+            );
+        }
+    }
+
+    let codons: Vec<&CodonSummary> = results.iter().map(|result| &result.codons).collect();
+    print_row("Synonymous codons", codons.iter().map(|c| c.synonymous.to_string()).collect());
+    print_row("Missense codons", codons.iter().map(|c| c.missense.to_string()).collect());
+    print_row("Nonsense codons", codons.iter().map(|c| c.nonsense.to_string()).collect());
+
+    let estimates: Vec<&DnDsEstimate> = results.iter().map(|result| &result.dn_ds).collect();
+    print_row("dN", estimates.iter().map(|e| format_table_estimate(e.dn)).collect());
+    print_row("dS", estimates.iter().map(|e| format_table_estimate(e.ds)).collect());
+    print_row("dN/dS", estimates.iter().map(|e| format_table_estimate(e.dn_ds)).collect());
+
+    print_row(
+        "Amino acid differences",
+        results.iter().map(|result| cell(result.amino_acid.as_ref().map(amino_acid_changes))).collect(),
+    );
+    print_row("GC Z-score", results.iter().map(|result| format!("{:.4}", result.gc_z_score)).collect());
+    print_row("GC P-value", results.iter().map(|result| format_p_value(result.gc_p_value)).collect());
+}
+
+fn print_row(measure: &str, cells: Vec<String>) {
+    println!("{}\t{}", measure, cells.join("\t"));
+}
+
+// Table cell, '-' where the alignment behind it was not run
+fn cell<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| value.to_string())
+}
+
+// Score, matches, mismatches and gaps of an alignment
+fn alignment_summary(alignment: Option<&AlignmentFile>) -> [String; 4] {
+    match alignment {
+        Some(alignment) => {
+            let (matches, mismatches, gaps) = alignment.counts();
+            [alignment.score.to_string(), matches.to_string(), mismatches.to_string(), gaps.to_string()]
+        }
+        None => ["-".to_string(), "-".to_string(), "-".to_string(), "-".to_string()],
+    }
+}
+
+// Amino acid differences as "K986P,V987P" (alignment column numbers)
+fn amino_acid_changes(alignment: &AlignmentFile) -> String {
+    let changes: Vec<String> = alignment
+        .differences()
+        .iter()
+        .map(|difference| format!("{}{}{}", difference.reference, difference.column, difference.query))
+        .collect();
+    if changes.is_empty() {
+        "none".to_string()
+    } else {
+        changes.join(",")
+    }
+}
+
+// Estimates in the summary table, where there is no room for the explanation
+fn format_table_estimate(value: f64) -> String {
+    if value.is_finite() {
+        format!("{:.4}", value)
+    } else {
+        "undefined".to_string()
+    }
+}
+
+fn format_p_value(p_value: f64) -> String {
+    if p_value == 0.0 {
+        "< 1e-10".to_string()
+    } else {
+        format!("{:.4}", p_value)
+    }
+}
+
+// Summarise the six frames, list the ORFs of a sequence and return the longest forward-strand
+// ORF, taken as the CDS. The inputs are mRNA or coding strand, and codon-optimised sequences
+// can have long ORFs on the antisense strand by chance.
+fn find_cds(file_path: &str, sequence: &str, pipeline: &Pipeline, code: &GeneticCode) -> Result<Orf, Box<dyn Error>> {
+    let settings = &pipeline.translation;
+    for translation in six_frame_translation(sequence, code) {
+        println!(
+            "{} frame {}{}: {} codons, {} stop codons",
+            file_path,
+            translation.strand.symbol(),
+            translation.frame,
+            translation.protein.len(),
+            translation.protein.matches('*').count()
+        );
+    }
+    let orfs = find_orfs(
+        sequence,
+        code,
+        settings.min_orf_length,
+        settings.alternative_starts,
+    );
+    println!("{}: {} ORFs", file_path, orfs.len());
+    for orf in &orfs {
+        println!("  {}", format_orf(orf));
+    }
+    let forward: Vec<Orf> = orfs.into_iter().filter(|orf| orf.strand == Strand::Forward).collect();
+    let cds = longest_orf(&forward).ok_or_else(|| {
+        format!(
+            "No forward-strand ORF of at least {} amino acids in '{}'.",
+            settings.min_orf_length, file_path
+        )
+    })?;
+    println!("Using the longest forward-strand ORF as the CDS: {}", format_orf(cds));
+    Ok(cds.clone())
+}
+
+// Share of most-frequent codons per sequence, overall and per amino acid
+fn print_codon_optimality(comparisons: &[(&str, OptimalCodonComparison)]) {
+    for (name, comparison) in comparisons {
+        println!(
+            "{}: {} of {} codons ({:.2}%) are the most frequent codon for their amino acid",
+            name,
+            comparison.optimal(),
+            comparison.choices.len(),
+            comparison.percent_optimal()
+        );
+    }
+    let names: Vec<&str> = comparisons.iter().map(|(name, _)| *name).collect();
+    println!("Most frequent codon used, per amino acid (optimal/total):");
+    println!("AA\t{}", names.join("\t"));
+    let amino_acids: BTreeSet<char> = comparisons
+        .iter()
+        .flat_map(|(_, comparison)| comparison.by_amino_acid.keys().copied())
+        .collect();
+    for amino_acid in amino_acids {
+        let cells: Vec<String> = comparisons
+            .iter()
+            .map(|(_, comparison)| match comparison.by_amino_acid.get(&amino_acid) {
+                Some((total, optimal)) => format!("{}/{}", optimal, total),
+                None => "-".to_string(),
+            })
+            .collect();
+        println!("{}\t{}", amino_acid, cells.join("\t"));
+    }
+}
+
+// Codon counts and RSCU of every codon, one column per sequence or table, grouped by amino acid
+fn print_codon_usage(usages: &[(&str, CodonUsage)], code: &GeneticCode) {
+    let names: Vec<&str> = usages.iter().map(|(name, _)| *name).collect();
+    let rscus: Vec<_> = usages.iter().map(|(_, usage)| usage.rscu(code)).collect();
+    println!("Codon usage (count or table frequency) and RSCU:");
+    let rscu_names: Vec<String> = names.iter().map(|name| format!("RSCU {}", name)).collect();
+    println!("Codon\tAA\t{}\t{}", names.join("\t"), rscu_names.join("\t"));
+    let mut codons: Vec<(char, &String)> = usages[0]
+        .1
+        .frequencies
+        .keys()
+        .map(|codon| (code.translate(codon).unwrap_or('X'), codon))
+        .collect();
+    codons.sort();
+    for (amino_acid, codon) in codons {
+        let counts: Vec<String> = usages.iter().map(|(_, usage)| format!("{}", usage.count(codon))).collect();
+        let rscu: Vec<String> = rscus
+            .iter()
+            .map(|rscu| if rscu[codon].is_nan() { "-".to_string() } else { format!("{:.2}", rscu[codon]) })
+            .collect();
+        println!("{}\t{}\t{}\t{}", codon, amino_acid, counts.join("\t"), rscu.join("\t"));
+    }
+}
+
+// One line per codon of every sequence: whether it is the most frequent codon
+fn write_codon_optimality(output_file: &str, comparisons: &[(&str, OptimalCodonComparison)]) -> Result<(), Box<dyn Error>> {
+    let mut file = create_output(output_file)?;
+    writeln!(file, "sequence\tcodon_number\tamino_acid\tcodon\tmost_frequent_codon\toptimal")?;
+    for (name, comparison) in comparisons {
+        for choice in &comparison.choices {
+            writeln!(
+                file,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                name,
+                choice.index,
+                choice.amino_acid,
+                choice.codon,
+                choice.optimal_codon,
+                if choice.is_optimal() { "yes" } else { "no" }
+            )?;
+        }
+    }
     Ok(())
 }
 
-fn read_alignment_score(file_path: &str) -> Result<i32, Box<dyn Error>> {
-    let file = fs::File::open(file_path)?;
-    let mut reader = io::BufReader::new(file);
-    let mut score_line = String::new();
-    reader.read_line(&mut score_line)?;
-    let score: i32 = score_line.trim().parse()?;
-    Ok(score)
+fn format_orf(orf: &Orf) -> String {
+    format!(
+        "strand {} frame {}, {}..{}, {} aa{}",
+        orf.strand.symbol(),
+        orf.frame,
+        orf.start,
+        orf.end,
+        orf.protein.len(),
+        if orf.has_stop { "" } else { ", no stop codon" }
+    )
 }
 
-fn parse_alignment(file_path: &str) -> Result<(usize, usize, usize), Box<dyn Error>> {
-    let file = fs::File::open(file_path)?;
-    let reader = io::BufReader::new(file);
-    let lines: Vec<String> = reader.lines().collect::<Result<_, _>>()?;
-    if lines.len() < 5 {
-        println!("File '{}' needs to have at least six lines.", file_path);
-        return Ok((0, 0, 0));
+// dN/dS estimates are NaN when the Jukes-Cantor correction is undefined (p >= 0.75)
+fn format_estimate(value: f64) -> String {
+    if value.is_finite() {
+        format!("{:.4}", value)
+    } else {
+        "undefined (saturated, p >= 0.75)".to_string()
     }
-    let visual_line = &lines[3];
-    let matches = visual_line.matches('|').count();
-    let mismatches = visual_line.matches('x').count();
-    let gaps = visual_line.matches(' ').count();
-    Ok((matches, mismatches, gaps))
 }
 
-fn extract_differences_aa(file_path: &str) -> Result<Vec<String>, Box<dyn Error>> {
-    let file = fs::File::open(file_path)?;
-    let reader = io::BufReader::new(file);
-    let mut lines = reader.lines();
-    lines.next();
-
-    let _header1 = lines.next().unwrap_or(Ok(String::new()))?;
-    let seq1 = lines.next().unwrap_or(Ok(String::new()))?;
-    let visual = lines.next().unwrap_or(Ok(String::new()))?;
-    let seq2 = lines.next().unwrap_or(Ok(String::new()))?;
-    let _header2 = lines.next().unwrap_or(Ok(String::new()))?;
-
-    let mut differences = Vec::new();
-    for (i, ((c1, c2), v)) in seq1
-        .chars()
-        .zip(seq2.chars())
-        .zip(visual.chars())
-        .enumerate()
-    {
-        if v != '|' {
-            differences.push(format!("Position {}: {} vs {}", i + 1, c1, c2));
+// 95% bootstrap interval as printed by the analysis
+fn format_interval(interval: &ConfidenceInterval) -> String {
+    if interval.lower.is_nan() {
+        return "(95% CI undefined in all bootstrap replicates)".to_string();
+    }
+    let mut text = format!("(95% CI {:.4} to {:.4})", interval.lower, interval.upper);
+    if interval.undefined > 0 {
+        text.push_str(&format!(", {} replicates undefined", interval.undefined));
+    }
+    text
+}
+
+// Modified bases of all records of a FASTA file
+fn count_modified_bases(file_path: &str, alphabet: &ModifiedBaseAlphabet) -> Result<ModifiedBaseCounts, Box<dyn Error>> {
+    let mut counts = ModifiedBaseCounts::new();
+    for record in open_fasta(file_path, alphabet)? {
+        for (symbol, count) in record?.modified_bases {
+            *counts.entry(symbol).or_insert(0) += count;
         }
     }
-    Ok(differences)
+    Ok(counts)
 }
 
-fn calculate_gc_content(file_path: &str) -> Result<(f64, f64, f64), Box<dyn Error>> {
-    let file = fs::File::open(file_path)?;
-    let reader = io::BufReader::new(file);
+fn calculate_gc_content(file_path: &str, alphabet: &ModifiedBaseAlphabet) -> Result<(f64, f64, f64), Box<dyn Error>> {
     let mut gc_count = 0.0;
     let mut total = 0.0;
-    for (i, line) in reader.lines().enumerate() {
-        let line = line?;
-        if i == 0 && line.starts_with('>') {
-            continue;
-        }
-        for c in line.chars() {
+    for record in open_fasta(file_path, alphabet)? {
+        for c in record?.sequence.chars() {
             match c.to_ascii_uppercase() {
                 'G' | 'C' => {
                     gc_count += 1.0;
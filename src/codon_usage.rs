@@ -0,0 +1,254 @@
+// Codon usage tables, back-translation to the most frequent codons, how closely a coding
+// sequence follows them, relative synonymous codon usage (RSCU) and the Codon Adaptation Index
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+
+use crate::genetic_code::GeneticCode;
+
+// Relative adaptiveness given to codons the reference table never uses, so that one of them
+// does not make the CAI zero
+const MIN_RELATIVE_ADAPTIVENESS: f64 = 0.01;
+
+// Frequency (or count) of every codon, keyed by uppercase DNA codon
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodonUsage {
+    pub frequencies: BTreeMap<String, f64>,
+}
+
+impl CodonUsage {
+    // Read a codon usage table. Every codon followed by a number is used, so tables in the
+    // Kazusa/GCG layout work as they are: "UUU 17.6(714298)", "UUU F 17.6 (714298)" or
+    // "TTT 17.6". Text after '#' is ignored. All 64 codons must be present.
+    pub fn read(file_path: &str) -> Result<CodonUsage, Box<dyn Error>> {
+        let text = fs::read_to_string(file_path)
+            .map_err(|e| format!("Cannot read codon usage table '{}': {}", file_path, e))?;
+        let mut frequencies = BTreeMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("");
+            let mut tokens = line.split_whitespace().peekable();
+            while let Some(token) = tokens.next() {
+                let codon = match dna_codon(token) {
+                    Some(codon) => codon,
+                    None => continue,
+                };
+                // Skip a one-letter amino acid column between codon and frequency
+                if tokens.peek().is_some_and(|next| next.len() == 1 && next.parse::<f64>().is_err()) {
+                    tokens.next();
+                }
+                let value = tokens
+                    .next()
+                    .and_then(|value| value.split('(').next())
+                    .and_then(|value| value.parse::<f64>().ok())
+                    .filter(|value| value.is_finite() && *value >= 0.0)
+                    .ok_or_else(|| format!("'{}' line {}: no frequency for codon {}.", file_path, number + 1, token))?;
+                frequencies.insert(codon, value);
+            }
+        }
+        if frequencies.len() != 64 {
+            return Err(format!(
+                "'{}' lists {} of the 64 codons; a codon usage table needs all of them.",
+                file_path,
+                frequencies.len()
+            )
+            .into());
+        }
+        Ok(CodonUsage { frequencies })
+    }
+
+    // Codon counts of the complete codons of a coding sequence; codons with other letters
+    // than A, C, G, T/U are not counted
+    pub fn from_coding_sequence(coding_sequence: &str) -> CodonUsage {
+        let mut frequencies: BTreeMap<String, f64> = all_codons().map(|codon| (codon, 0.0)).collect();
+        for codon in coding_sequence.as_bytes().chunks_exact(3) {
+            if let Some(codon) = dna_codon(&String::from_utf8_lossy(codon)) {
+                *frequencies.get_mut(&codon).unwrap() += 1.0;
+            }
+        }
+        CodonUsage { frequencies }
+    }
+
+    pub fn count(&self, codon: &str) -> f64 {
+        dna_codon(codon)
+            .and_then(|codon| self.frequencies.get(&codon).copied())
+            .unwrap_or(0.0)
+    }
+
+    // RSCU of every codon: its frequency over the mean frequency of the codons for the same
+    // amino acid (1.0 when all are used equally). NaN when the amino acid does not occur.
+    pub fn rscu(&self, genetic_code: &GeneticCode) -> BTreeMap<String, f64> {
+        self.frequencies
+            .iter()
+            .map(|(codon, frequency)| {
+                let family = synonymous_codons(codon, genetic_code);
+                let total: f64 = family.iter().map(|synonym| self.count(synonym)).sum();
+                let rscu = if total > 0.0 { frequency * family.len() as f64 / total } else { f64::NAN };
+                (codon.clone(), rscu)
+            })
+            .collect()
+    }
+
+    // Relative adaptiveness w of every codon: its frequency over that of the most frequent
+    // codon for the same amino acid, at least MIN_RELATIVE_ADAPTIVENESS
+    pub fn relative_adaptiveness(&self, genetic_code: &GeneticCode) -> BTreeMap<String, f64> {
+        self.frequencies
+            .iter()
+            .map(|(codon, frequency)| {
+                let best = synonymous_codons(codon, genetic_code)
+                    .iter()
+                    .map(|synonym| self.count(synonym))
+                    .fold(0.0, f64::max);
+                let w = if best > 0.0 { frequency / best } else { 1.0 };
+                (codon.clone(), w.max(MIN_RELATIVE_ADAPTIVENESS))
+            })
+            .collect()
+    }
+
+    // Most frequent codon of every amino acid (and '*'), the first in alphabetical order on a tie
+    pub fn preferred_codons(&self, genetic_code: &GeneticCode) -> BTreeMap<char, String> {
+        let mut preferred: BTreeMap<char, (String, f64)> = BTreeMap::new();
+        for (codon, frequency) in &self.frequencies {
+            let amino_acid = match genetic_code.translate(codon) {
+                Some(amino_acid) => amino_acid,
+                None => continue,
+            };
+            match preferred.get(&amino_acid) {
+                Some((_, best)) if best >= frequency => {}
+                _ => {
+                    preferred.insert(amino_acid, (codon.clone(), *frequency));
+                }
+            }
+        }
+        preferred.into_iter().map(|(amino_acid, (codon, _))| (amino_acid, codon)).collect()
+    }
+}
+
+// Back-translate a protein, choosing the most frequent codon of the table for every residue
+pub fn back_translate(protein: &str, usage: &CodonUsage, genetic_code: &GeneticCode) -> Result<String, Box<dyn Error>> {
+    let preferred = usage.preferred_codons(genetic_code);
+    let mut coding_sequence = String::with_capacity(protein.len() * 3);
+    for (position, amino_acid) in protein.chars().enumerate() {
+        let codon = preferred
+            .get(&amino_acid.to_ascii_uppercase())
+            .ok_or_else(|| format!("Cannot back-translate '{}' at residue {}.", amino_acid, position + 1))?;
+        coding_sequence.push_str(codon);
+    }
+    Ok(coding_sequence)
+}
+
+// Codon Adaptation Index (Sharp and Li, 1987) of a coding sequence against a reference usage
+// table: the geometric mean of the relative adaptiveness of its codons. Stop codons and amino
+// acids with a single codon (M and W in the standard code) carry no information and are left
+// out. NaN if no codon is left.
+pub fn codon_adaptation_index(coding_sequence: &str, reference: &CodonUsage, genetic_code: &GeneticCode) -> f64 {
+    let weights = reference.relative_adaptiveness(genetic_code);
+    let mut log_sum = 0.0;
+    let mut codons = 0;
+    for codon in coding_sequence.as_bytes().chunks_exact(3) {
+        let codon = match dna_codon(&String::from_utf8_lossy(codon)) {
+            Some(codon) => codon,
+            None => continue,
+        };
+        if genetic_code.is_stop(&codon) || synonymous_codons(&codon, genetic_code).len() < 2 {
+            continue;
+        }
+        log_sum += weights[&codon].ln();
+        codons += 1;
+    }
+    if codons == 0 {
+        f64::NAN
+    } else {
+        (log_sum / codons as f64).exp()
+    }
+}
+
+// Codons for the same amino acid (or stop) as `codon`, itself included
+pub fn synonymous_codons(codon: &str, genetic_code: &GeneticCode) -> Vec<String> {
+    let amino_acid = genetic_code.translate(codon);
+    all_codons().filter(|synonym| genetic_code.translate(synonym) == amino_acid).collect()
+}
+
+// One codon of a coding sequence next to the most frequent codon for its amino acid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodonChoice {
+    // 1-based codon number
+    pub index: usize,
+    pub amino_acid: char,
+    pub codon: String,
+    pub optimal_codon: String,
+}
+
+impl CodonChoice {
+    pub fn is_optimal(&self) -> bool {
+        self.codon == self.optimal_codon
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptimalCodonComparison {
+    pub choices: Vec<CodonChoice>,
+    // Amino acid -> (codons, optimal codons)
+    pub by_amino_acid: BTreeMap<char, (usize, usize)>,
+}
+
+impl OptimalCodonComparison {
+    pub fn optimal(&self) -> usize {
+        self.choices.iter().filter(|choice| choice.is_optimal()).count()
+    }
+
+    // Percentage of codons that are the optimal choice, 0 without codons
+    pub fn percent_optimal(&self) -> f64 {
+        if self.choices.is_empty() {
+            0.0
+        } else {
+            100.0 * self.optimal() as f64 / self.choices.len() as f64
+        }
+    }
+}
+
+// Compare every complete codon of a coding sequence with the most frequent codon of the table
+// for the amino acid it encodes. Codons that do not translate (ambiguous bases) are skipped.
+pub fn compare_to_optimal(coding_sequence: &str, usage: &CodonUsage, genetic_code: &GeneticCode) -> OptimalCodonComparison {
+    let preferred = usage.preferred_codons(genetic_code);
+    let mut choices = Vec::new();
+    let mut by_amino_acid = BTreeMap::new();
+    for (index, codon) in coding_sequence.as_bytes().chunks_exact(3).enumerate() {
+        let codon = match dna_codon(&String::from_utf8_lossy(codon)) {
+            Some(codon) => codon,
+            None => continue,
+        };
+        let amino_acid = match genetic_code.translate(&codon) {
+            Some(amino_acid) => amino_acid,
+            None => continue,
+        };
+        let choice = CodonChoice {
+            index: index + 1,
+            amino_acid,
+            codon,
+            optimal_codon: preferred[&amino_acid].clone(),
+        };
+        let counts = by_amino_acid.entry(amino_acid).or_insert((0, 0));
+        counts.0 += 1;
+        if choice.is_optimal() {
+            counts.1 += 1;
+        }
+        choices.push(choice);
+    }
+    OptimalCodonComparison { choices, by_amino_acid }
+}
+
+// The 64 codons in alphabetical order
+fn all_codons() -> impl Iterator<Item = String> {
+    const BASES: [char; 4] = ['A', 'C', 'G', 'T'];
+    (0..64).map(|i| [BASES[i / 16], BASES[i / 4 % 4], BASES[i % 4]].iter().collect())
+}
+
+// Uppercase DNA form of a codon of A, C, G, T/U; None for anything else
+fn dna_codon(codon: &str) -> Option<String> {
+    let codon = codon.to_ascii_uppercase().replace('U', "T");
+    if codon.len() == 3 && codon.chars().all(|base| matches!(base, 'A' | 'C' | 'G' | 'T')) {
+        Some(codon)
+    } else {
+        None
+    }
+}
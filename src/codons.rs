@@ -0,0 +1,222 @@
+// Codon-level comparison of a nucleotide alignment whose reference is a coding sequence
+use crate::alignment_file::{AlignmentFile, MARKUP_GAP, MARKUP_MATCH, MARKUP_MISMATCH, TEXT_GAP};
+use crate::genetic_code::GeneticCode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodonChange {
+    Synonymous, // Same amino acid
+    Missense,   // Different amino acid (including a lost stop codon)
+    Nonsense,   // Amino acid replaced by a stop codon
+}
+
+// One reference codon and the query bases aligned to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodonPair {
+    pub index: usize, // 1-based codon number in the reference
+    pub reference: String,
+    // None when the codon's columns contain a gap, so the query has no matching codon
+    pub query: Option<String>,
+}
+
+// A reference codon that differs from its query codon
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodonDifference {
+    pub index: usize,
+    pub reference_codon: String,
+    pub query_codon: String,
+    pub reference_amino_acid: char,
+    pub query_amino_acid: char,
+    pub change: CodonChange,
+}
+
+// Codon counts per category
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CodonSummary {
+    pub identical: usize,
+    pub synonymous: usize,
+    pub missense: usize,
+    pub nonsense: usize,
+    // Codons with a gap or a base outside ACGT/U
+    pub unclassified: usize,
+}
+
+// Codon alignment of two coding sequences, threaded onto the alignment of their proteins:
+// each residue column takes the residue's codon and each gap column a gap triplet, so indels
+// are whole codons and every codon stays in frame. The proteins must be the translations of
+// the coding sequences, one residue per complete codon (stop codons included).
+pub fn codon_alignment(
+    reference_cds: &str,
+    query_cds: &str,
+    protein_alignment: &AlignmentFile,
+) -> Result<AlignmentFile, String> {
+    let reference_aligned = thread_codons(reference_cds, &protein_alignment.reference_aligned, "reference")?;
+    let query_aligned = thread_codons(query_cds, &protein_alignment.query_aligned, "query")?;
+    let markup = reference_aligned
+        .chars()
+        .zip(query_aligned.chars())
+        .map(|(r, q)| {
+            if r == TEXT_GAP || q == TEXT_GAP {
+                MARKUP_GAP
+            } else if r.eq_ignore_ascii_case(&q) {
+                MARKUP_MATCH
+            } else {
+                MARKUP_MISMATCH
+            }
+        })
+        .collect();
+    Ok(AlignmentFile {
+        score: protein_alignment.score,
+        reference_header: protein_alignment.reference_header.clone(),
+        reference_aligned,
+        markup,
+        query_aligned,
+        query_header: protein_alignment.query_header.clone(),
+    })
+}
+
+// Replace every residue of an aligned protein with the next codon of its coding sequence
+fn thread_codons(cds: &str, aligned_protein: &str, name: &str) -> Result<String, String> {
+    let bases: Vec<char> = cds.chars().collect();
+    let mut codons = bases.chunks_exact(3);
+    let mut aligned = String::new();
+    for residue in aligned_protein.chars() {
+        if residue == TEXT_GAP {
+            aligned.extend([TEXT_GAP; 3]);
+            continue;
+        }
+        let codon = codons.next().ok_or_else(|| {
+            format!("the {} protein has more residues than its coding sequence has codons ({})", name, bases.len() / 3)
+        })?;
+        aligned.extend(codon);
+    }
+    if codons.next().is_some() {
+        return Err(format!(
+            "the {} protein has fewer residues than its coding sequence has codons ({})",
+            name,
+            bases.len() / 3
+        ));
+    }
+    Ok(aligned)
+}
+
+// Split the alignment into reference codons (phase taken from reference position 0) and
+// collect the query bases in the same columns. Insertions inside a codon count as gaps.
+pub fn codon_pairs(alignment: &AlignmentFile) -> Vec<CodonPair> {
+    let mut pairs = Vec::new();
+    let mut reference_codon = String::new();
+    let mut query_codon = String::new();
+    let mut gapped = false;
+
+    for (r, q) in alignment.reference_aligned.chars().zip(alignment.query_aligned.chars()) {
+        if r == TEXT_GAP {
+            // Insertion: breaks the codon only if it falls inside one
+            if !reference_codon.is_empty() {
+                gapped = true;
+            }
+            continue;
+        }
+        reference_codon.push(r.to_ascii_uppercase());
+        if q == TEXT_GAP {
+            gapped = true;
+        } else {
+            query_codon.push(q.to_ascii_uppercase());
+        }
+        if reference_codon.len() == 3 {
+            pairs.push(CodonPair {
+                index: pairs.len() + 1,
+                reference: std::mem::take(&mut reference_codon),
+                query: if gapped { None } else { Some(query_codon.clone()) },
+            });
+            query_codon.clear();
+            gapped = false;
+        }
+    }
+    pairs
+}
+
+// Classify every differing codon as synonymous, missense or nonsense
+pub fn classify_codons(alignment: &AlignmentFile, genetic_code: &GeneticCode) -> (Vec<CodonDifference>, CodonSummary) {
+    let mut differences = Vec::new();
+    let mut summary = CodonSummary::default();
+
+    for pair in codon_pairs(alignment) {
+        let query = match &pair.query {
+            Some(query) => query,
+            None => {
+                summary.unclassified += 1;
+                continue;
+            }
+        };
+        if *query == pair.reference {
+            summary.identical += 1;
+            continue;
+        }
+        let (reference_amino_acid, query_amino_acid) =
+            match (genetic_code.translate(&pair.reference), genetic_code.translate(query)) {
+                (Some(r), Some(q)) => (r, q),
+                _ => {
+                    summary.unclassified += 1;
+                    continue;
+                }
+            };
+
+        let change = if reference_amino_acid == query_amino_acid {
+            summary.synonymous += 1;
+            CodonChange::Synonymous
+        } else if query_amino_acid == '*' {
+            summary.nonsense += 1;
+            CodonChange::Nonsense
+        } else {
+            summary.missense += 1;
+            CodonChange::Missense
+        };
+        differences.push(CodonDifference {
+            index: pair.index,
+            reference_codon: pair.reference.clone(),
+            query_codon: query.clone(),
+            reference_amino_acid,
+            query_amino_acid,
+            change,
+        });
+    }
+    (differences, summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genetic_code::standard_code;
+
+    fn protein_alignment(reference: &str, query: &str) -> AlignmentFile {
+        AlignmentFile {
+            score: 0.0,
+            reference_header: ">reference".to_string(),
+            reference_aligned: reference.to_string(),
+            markup: String::new(),
+            query_aligned: query.to_string(),
+            query_header: ">query".to_string(),
+        }
+    }
+
+    #[test]
+    fn codon_alignment_inserts_gaps_as_whole_codons() {
+        // M K P * against M P *: the lysine codon is deleted as one triplet
+        let alignment = codon_alignment("ATGAAACCCTAA", "ATGCCGTGA", &protein_alignment("MKP*", "M_P*")).unwrap();
+        assert_eq!(alignment.reference_aligned, "ATGAAACCCTAA");
+        assert_eq!(alignment.query_aligned, "ATG___CCGTGA");
+        assert_eq!(alignment.markup, "|||   ||x|x|");
+
+        let (differences, summary) = classify_codons(&alignment, standard_code());
+        assert_eq!(summary.identical, 1);
+        assert_eq!(summary.synonymous, 2);
+        assert_eq!(summary.missense, 0);
+        assert_eq!(summary.unclassified, 1);
+        assert!(differences.iter().all(|d| d.change == CodonChange::Synonymous));
+    }
+
+    #[test]
+    fn codon_alignment_rejects_a_protein_that_does_not_fit() {
+        assert!(codon_alignment("ATGAAA", "ATGAAA", &protein_alignment("MKP", "MK_")).is_err());
+        assert!(codon_alignment("ATGAAACCC", "ATGAAA", &protein_alignment("MK", "MK")).is_err());
+    }
+}
@@ -0,0 +1,251 @@
+// dN/dS between two aligned coding sequences: Nei-Gojobori (1986) site and difference
+// counts with Jukes-Cantor correction, and bootstrap confidence intervals over codons
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::alignment_file::AlignmentFile;
+use crate::codons::codon_pairs;
+use crate::genetic_code::GeneticCode;
+
+const BASES: [char; 4] = ['A', 'C', 'G', 'T'];
+
+// Site and difference counts of one codon pair, or summed over many
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SiteCounts {
+    pub synonymous_sites: f64,
+    pub nonsynonymous_sites: f64,
+    pub synonymous_differences: f64,
+    pub nonsynonymous_differences: f64,
+}
+
+impl SiteCounts {
+    fn add(&mut self, other: &SiteCounts) {
+        self.synonymous_sites += other.synonymous_sites;
+        self.nonsynonymous_sites += other.nonsynonymous_sites;
+        self.synonymous_differences += other.synonymous_differences;
+        self.nonsynonymous_differences += other.nonsynonymous_differences;
+    }
+
+    // Proportion of differing synonymous sites (pS)
+    pub fn p_synonymous(&self) -> f64 {
+        self.synonymous_differences / self.synonymous_sites
+    }
+
+    // Proportion of differing non-synonymous sites (pN)
+    pub fn p_nonsynonymous(&self) -> f64 {
+        self.nonsynonymous_differences / self.nonsynonymous_sites
+    }
+
+    // Jukes-Cantor corrected substitutions per synonymous site
+    pub fn ds(&self) -> f64 {
+        jukes_cantor(self.p_synonymous())
+    }
+
+    // Jukes-Cantor corrected substitutions per non-synonymous site
+    pub fn dn(&self) -> f64 {
+        jukes_cantor(self.p_nonsynonymous())
+    }
+
+    // NaN when dS is undefined or zero
+    pub fn dn_ds(&self) -> f64 {
+        let ds = self.ds();
+        if ds.is_finite() && ds > 0.0 {
+            self.dn() / ds
+        } else {
+            f64::NAN
+        }
+    }
+}
+
+// 2.5th and 97.5th percentile of the bootstrap replicates that gave a finite value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfidenceInterval {
+    pub lower: f64,
+    pub upper: f64,
+    // Replicates left out because the estimate was undefined (e.g. p >= 0.75)
+    pub undefined: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnDsEstimate {
+    pub codons: usize,
+    pub counts: SiteCounts,
+    pub dn: f64,
+    pub ds: f64,
+    pub dn_ds: f64,
+    pub dn_interval: ConfidenceInterval,
+    pub ds_interval: ConfidenceInterval,
+    pub dn_ds_interval: ConfidenceInterval,
+}
+
+// Estimate dN/dS over the codons of the alignment that are complete in both sequences
+// and are not stop codons, with `replicates` bootstrap resamplings of those codons. Pass a
+// codon alignment (`codons::codon_alignment`) so that no gap splits a codon.
+pub fn estimate_dn_ds(alignment: &AlignmentFile, genetic_code: &GeneticCode, replicates: usize, seed: u64) -> DnDsEstimate {
+    let per_codon: Vec<SiteCounts> = codon_pairs(alignment)
+        .into_iter()
+        .filter_map(|pair| {
+            let query = pair.query?;
+            let reference_amino_acid = genetic_code.translate(&pair.reference)?;
+            let query_amino_acid = genetic_code.translate(&query)?;
+            if reference_amino_acid == '*' || query_amino_acid == '*' {
+                return None;
+            }
+            let (reference, query) = (pair.reference.replace('U', "T"), query.replace('U', "T"));
+            Some(codon_pair_counts(genetic_code, &reference, &query))
+        })
+        .collect();
+
+    let counts = sum_counts(per_codon.iter());
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut dn_values = Vec::with_capacity(replicates);
+    let mut ds_values = Vec::with_capacity(replicates);
+    let mut ratio_values = Vec::with_capacity(replicates);
+    for _ in 0..replicates {
+        let sample = sum_counts((0..per_codon.len()).map(|_| &per_codon[rng.gen_range(0..per_codon.len())]));
+        dn_values.push(sample.dn());
+        ds_values.push(sample.ds());
+        ratio_values.push(sample.dn_ds());
+    }
+
+    DnDsEstimate {
+        codons: per_codon.len(),
+        counts,
+        dn: counts.dn(),
+        ds: counts.ds(),
+        dn_ds: counts.dn_ds(),
+        dn_interval: percentile_interval(dn_values),
+        ds_interval: percentile_interval(ds_values),
+        dn_ds_interval: percentile_interval(ratio_values),
+    }
+}
+
+fn sum_counts<'a>(counts: impl Iterator<Item = &'a SiteCounts>) -> SiteCounts {
+    let mut total = SiteCounts::default();
+    for codon in counts {
+        total.add(codon);
+    }
+    total
+}
+
+// Sites are averaged over both codons; differences are averaged over all orders in which
+// the differing positions can change, skipping orders that pass through a stop codon
+fn codon_pair_counts(genetic_code: &GeneticCode, reference: &str, query: &str) -> SiteCounts {
+    let reference_sites = synonymous_sites(genetic_code, reference);
+    let query_sites = synonymous_sites(genetic_code, query);
+    let synonymous_sites = (reference_sites + query_sites) / 2.0;
+    let (synonymous_differences, nonsynonymous_differences) = codon_differences(genetic_code, reference, query);
+    SiteCounts {
+        synonymous_sites,
+        nonsynonymous_sites: 3.0 - synonymous_sites,
+        synonymous_differences,
+        nonsynonymous_differences,
+    }
+}
+
+// Synonymous sites of a codon: a third for every single-base change that keeps the
+// amino acid. Changes to a stop codon count as non-synonymous.
+fn synonymous_sites(genetic_code: &GeneticCode, codon: &str) -> f64 {
+    let amino_acid = genetic_code.translate(codon);
+    let mut sites = 0.0;
+    let mut bases: Vec<char> = codon.chars().collect();
+    for position in 0..3 {
+        let original = bases[position];
+        for base in BASES.iter().filter(|base| **base != original) {
+            bases[position] = *base;
+            let mutant: String = bases.iter().collect();
+            if genetic_code.translate(&mutant) == amino_acid {
+                sites += 1.0 / 3.0;
+            }
+        }
+        bases[position] = original;
+    }
+    sites
+}
+
+// Synonymous and non-synonymous differences between two codons
+fn codon_differences(genetic_code: &GeneticCode, reference: &str, query: &str) -> (f64, f64) {
+    let reference: Vec<char> = reference.chars().collect();
+    let query: Vec<char> = query.chars().collect();
+    let differing: Vec<usize> = (0..3).filter(|i| reference[*i] != query[*i]).collect();
+    if differing.is_empty() {
+        return (0.0, 0.0);
+    }
+
+    let mut synonymous = 0.0;
+    let mut nonsynonymous = 0.0;
+    let mut pathways = 0;
+    for order in permutations(&differing) {
+        let mut current = reference.clone();
+        let mut path_synonymous = 0.0;
+        let mut path_nonsynonymous = 0.0;
+        let mut through_stop = false;
+        for (step, position) in order.iter().enumerate() {
+            let before = genetic_code.translate(&current.iter().collect::<String>());
+            current[*position] = query[*position];
+            let after = genetic_code.translate(&current.iter().collect::<String>());
+            if after == Some('*') && step + 1 < order.len() {
+                through_stop = true;
+                break;
+            }
+            if before == after {
+                path_synonymous += 1.0;
+            } else {
+                path_nonsynonymous += 1.0;
+            }
+        }
+        if !through_stop {
+            synonymous += path_synonymous;
+            nonsynonymous += path_nonsynonymous;
+            pathways += 1;
+        }
+    }
+    if pathways == 0 {
+        // Every order passes through a stop codon; count all differences as non-synonymous
+        return (0.0, differing.len() as f64);
+    }
+    (synonymous / pathways as f64, nonsynonymous / pathways as f64)
+}
+
+fn permutations(items: &[usize]) -> Vec<Vec<usize>> {
+    if items.len() <= 1 {
+        return vec![items.to_vec()];
+    }
+    let mut result = Vec::new();
+    for (i, first) in items.iter().enumerate() {
+        let mut rest = items.to_vec();
+        rest.remove(i);
+        for mut tail in permutations(&rest) {
+            tail.insert(0, *first);
+            result.push(tail);
+        }
+    }
+    result
+}
+
+// Jukes-Cantor distance; NaN when p >= 0.75 and the correction is undefined
+fn jukes_cantor(p: f64) -> f64 {
+    let argument = 1.0 - 4.0 * p / 3.0;
+    if argument <= 0.0 || argument.is_nan() {
+        f64::NAN
+    } else {
+        -0.75 * argument.ln()
+    }
+}
+
+fn percentile_interval(values: Vec<f64>) -> ConfidenceInterval {
+    let total = values.len();
+    let mut finite: Vec<f64> = values.into_iter().filter(|value| value.is_finite()).collect();
+    let undefined = total - finite.len();
+    if finite.is_empty() {
+        return ConfidenceInterval { lower: f64::NAN, upper: f64::NAN, undefined };
+    }
+    finite.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let at = |quantile: f64| finite[((finite.len() - 1) as f64 * quantile).round() as usize];
+    ConfidenceInterval {
+        lower: at(0.025),
+        upper: at(0.975),
+        undefined,
+    }
+}
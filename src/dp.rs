@@ -0,0 +1,133 @@
+use crate::score::{add, max, Score, ScoreOverflow};
+
+// One step of an alignment path through the DP matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlignOp {
+    Diagonal, // Item i of the first input aligned to item j of the second
+    Up,       // Item i of the first input against a gap
+    Left,     // Item j of the second input against a gap
+}
+
+// Global/semi-global dynamic programming shared by sequence and profile alignment.
+// `pair_score(i, j)` scores aligning item i of the first input with item j of the second,
+// `up_gap(i)` and `left_gap(j)` score placing that item against a gap (0-based indices).
+// Returns the best score and the path from the start of both inputs to the cell the
+// traceback started from. With unpenalized end gaps that cell may lie before the end of
+// one input; the items after it are not part of the path.
+pub fn align_dp<S, P, U, L>(
+    m: usize,
+    n: usize,
+    pair_score: P,
+    up_gap: U,
+    left_gap: L,
+    unpenalized_end_gaps: bool,
+) -> Result<(S, Vec<AlignOp>), ScoreOverflow>
+where
+    S: Score,
+    P: Fn(usize, usize) -> S,
+    U: Fn(usize) -> S,
+    L: Fn(usize) -> S,
+{
+    // Init score and traceback matrices
+    // The first row and column are initialized based on whether end gaps are penalized
+    let mut score_matrix = vec![vec![S::zero(); n + 1]; m + 1];
+    let mut trace_matrix = vec![vec![' '; n + 1]; m + 1];
+
+    // Init first row and column
+    for i in 1..=m {
+        score_matrix[i][0] = if unpenalized_end_gaps { S::zero() } else { add(score_matrix[i - 1][0], up_gap(i - 1))? };
+        trace_matrix[i][0] = 'U'; // Up
+    }
+    for j in 1..=n {
+        score_matrix[0][j] = if unpenalized_end_gaps { S::zero() } else { add(score_matrix[0][j - 1], left_gap(j - 1))? };
+        trace_matrix[0][j] = 'L'; // Left
+    }
+    trace_matrix[0][0] = '0';
+
+    // Fill score and traceback matrices
+    for i in 1..=m {
+        for j in 1..=n {
+            // Diagonal score, adding match/mismatch score to the diagonal element
+            let diag_score = add(score_matrix[i - 1][j - 1], pair_score(i - 1, j - 1))?;
+
+            // Calculate score for moving up (gap in seq 2), penalize unless unpenalized end gap (which makes # of gaps potentially different from gaps with penalty)
+            let up_score = add(score_matrix[i - 1][j], if unpenalized_end_gaps && (i == m) { S::zero() } else { up_gap(i - 1) })?;
+
+            // Calculate score for moving left (gap in seq 1), penalize unless unpenalized end gap
+            let left_score = add(score_matrix[i][j - 1], if unpenalized_end_gaps && (j == n) { S::zero() } else { left_gap(j - 1) })?;
+
+            // Select the max score among diagonal, up, and left moves
+            let max_score = max(max(diag_score, up_score), left_score);
+            score_matrix[i][j] = max_score;
+
+            // Set trace matrix with D for diagonal, U for up, and L for left
+            if max_score == diag_score {
+                trace_matrix[i][j] = 'D'; // Diagonal
+            } else if max_score == up_score {
+                trace_matrix[i][j] = 'U'; // Up
+            } else {
+                trace_matrix[i][j] = 'L'; // Left
+            }
+        }
+    }
+
+    // Determine the traceback starting point
+    let (best_score, (start_i, start_j)) = if unpenalized_end_gaps {
+        // Semi-global alignment to find the maximum score in the last row and column
+        find_max_in_last_row_and_column(&score_matrix, m, n)
+    } else {
+        // Global alignment using the bottom-right corner
+        (score_matrix[m][n], (m, n))
+    };
+
+    // Traceback
+    let mut path = Vec::with_capacity(m + n);
+    let mut i = start_i;
+    let mut j = start_j;
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 && trace_matrix[i][j] == 'D' {
+            path.push(AlignOp::Diagonal);
+            i -= 1;
+            j -= 1;
+        } else if i > 0 && trace_matrix[i][j] == 'U' {
+            path.push(AlignOp::Up);
+            i -= 1;
+        } else if j > 0 && trace_matrix[i][j] == 'L' {
+            path.push(AlignOp::Left);
+            j -= 1;
+        } else {
+            break;
+        }
+    }
+    path.reverse();
+
+    Ok((best_score, path))
+}
+
+// Helper function to find the max score in the last row and last column
+fn find_max_in_last_row_and_column<S: Score>(
+    score_matrix: &[Vec<S>],
+    m: usize,
+    n: usize,
+) -> (S, (usize, usize)) {
+    let mut max_score = score_matrix[m][n];
+    let mut max_pos = (m, n);
+
+    // Check last row
+    for (j, &score) in score_matrix[m].iter().enumerate() {
+        if score > max_score {
+            max_score = score;
+            max_pos = (m, j);
+        }
+    }
+
+    // Check last column
+    for (i, row) in score_matrix.iter().enumerate() {
+        if row[n] > max_score {
+            max_score = row[n];
+            max_pos = (i, n);
+        }
+    }
+
+    (max_score, max_pos)
+}
@@ -0,0 +1,287 @@
+// Data shared by the alignment output formats: input records, CIGAR and coordinates
+
+// Gap character in the aligner's alignment strings
+pub const ALIGNMENT_GAP: char = '_';
+
+// Input records of a pairwise alignment. Headers are FASTA header lines including '>'.
+pub struct AlignmentInput<'a> {
+    pub reference_header: &'a str,
+    pub reference_sequence: &'a str,
+    pub query_header: &'a str,
+    pub query_sequence: &'a str,
+}
+
+impl AlignmentInput<'_> {
+    pub fn reference_id(&self) -> &str {
+        record_id(self.reference_header)
+    }
+
+    pub fn query_id(&self) -> &str {
+        record_id(self.query_header)
+    }
+}
+
+// Record id: the first word of a FASTA header line
+pub fn record_id(header: &str) -> &str {
+    header.trim_start_matches('>').split_whitespace().next().unwrap_or("")
+}
+
+// Record description: the rest of a FASTA header line after the id
+pub fn record_description(header: &str) -> &str {
+    let header = header.trim_start_matches('>').trim();
+    header.split_once(char::is_whitespace).map_or("", |(_, description)| description.trim())
+}
+
+// Scoring parameters the alignment was run with, for formats that report them
+pub struct AlignmentParameters<'a> {
+    pub match_score: f64,
+    pub mismatch_penalty: f64,
+    pub gap_penalty: f64,
+    pub unpenalized_end_gaps: bool,
+    pub sequence_type: &'a str,
+    pub score_type: &'a str,
+}
+
+// Column counts over the whole alignment, end gaps included
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlignmentStats {
+    pub length: usize,
+    pub matches: usize,
+    pub mismatches: usize,
+    pub gaps: usize,
+}
+
+impl AlignmentStats {
+    pub fn from_alignment(reference_aligned: &str, query_aligned: &str) -> AlignmentStats {
+        let mut stats = AlignmentStats { length: 0, matches: 0, mismatches: 0, gaps: 0 };
+        for (r, q) in reference_aligned.chars().zip(query_aligned.chars()) {
+            stats.length += 1;
+            if r == ALIGNMENT_GAP || q == ALIGNMENT_GAP {
+                stats.gaps += 1;
+            } else if r.eq_ignore_ascii_case(&q) {
+                stats.matches += 1;
+            } else {
+                stats.mismatches += 1;
+            }
+        }
+        stats
+    }
+
+    // Percentage of alignment columns, 0 for an empty alignment
+    pub fn percent(&self, count: usize) -> f64 {
+        if self.length == 0 {
+            0.0
+        } else {
+            100.0 * count as f64 / self.length as f64
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CigarOp {
+    Equal,     // '=' sequence match
+    Diff,      // 'X' sequence mismatch
+    Insertion, // 'I' query base absent from the reference
+    Deletion,  // 'D' reference base absent from the query
+    SoftClip,  // 'S' query base outside the aligned region
+}
+
+impl CigarOp {
+    pub fn symbol(self) -> char {
+        match self {
+            CigarOp::Equal => '=',
+            CigarOp::Diff => 'X',
+            CigarOp::Insertion => 'I',
+            CigarOp::Deletion => 'D',
+            CigarOp::SoftClip => 'S',
+        }
+    }
+}
+
+// Aligned region of a pairwise alignment, in the style of read mappers: end gaps are
+// trimmed, so the region starts and ends with aligned residues. Query bases outside it
+// are soft clipped. Coordinates are 0-based, ends exclusive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlignmentSpan {
+    pub reference_start: usize,
+    pub reference_end: usize,
+    pub query_start: usize,
+    pub query_end: usize,
+    pub query_length: usize,
+    // Run-length encoded operations, including soft clips
+    pub cigar: Vec<(CigarOp, usize)>,
+    pub matches: usize,
+    pub mismatches: usize,
+    pub inserted_bases: usize,
+    pub deleted_bases: usize,
+    // MD tag: matching run lengths, mismatched reference bases and ^deleted reference bases
+    pub md: String,
+}
+
+impl AlignmentSpan {
+    // Build the span from the aligned reference and query strings ('_' gaps).
+    // `query_length` is the full query length, which can exceed the aligned part when
+    // free end gaps let the alignment stop early.
+    pub fn from_alignment(reference_aligned: &str, query_aligned: &str, query_length: usize) -> AlignmentSpan {
+        let columns: Vec<(char, char)> = reference_aligned.chars().zip(query_aligned.chars()).collect();
+        let is_aligned = |(r, q): &(char, char)| *r != ALIGNMENT_GAP && *q != ALIGNMENT_GAP;
+        let first = columns.iter().position(is_aligned);
+        let last = columns.iter().rposition(is_aligned);
+
+        // Residues consumed by the columns before the aligned region
+        let mut reference_start = 0;
+        let mut query_start = 0;
+        for (r, q) in &columns[..first.unwrap_or(columns.len())] {
+            if *r != ALIGNMENT_GAP {
+                reference_start += 1;
+            }
+            if *q != ALIGNMENT_GAP {
+                query_start += 1;
+            }
+        }
+
+        let mut span = AlignmentSpan {
+            reference_start,
+            reference_end: reference_start,
+            query_start,
+            query_end: query_start,
+            query_length,
+            cigar: Vec::new(),
+            matches: 0,
+            mismatches: 0,
+            inserted_bases: 0,
+            deleted_bases: 0,
+            md: String::new(),
+        };
+        span.push_op(CigarOp::SoftClip, query_start);
+
+        let mut md_run = 0;
+        let mut in_deletion = false;
+        if let (Some(first), Some(last)) = (first, last) {
+            for (r, q) in &columns[first..=last] {
+                let op = if *r == ALIGNMENT_GAP {
+                    CigarOp::Insertion
+                } else if *q == ALIGNMENT_GAP {
+                    CigarOp::Deletion
+                } else if r.eq_ignore_ascii_case(q) {
+                    CigarOp::Equal
+                } else {
+                    CigarOp::Diff
+                };
+                span.push_op(op, 1);
+
+                match op {
+                    CigarOp::Equal => {
+                        span.matches += 1;
+                        md_run += 1;
+                        in_deletion = false;
+                    }
+                    CigarOp::Diff => {
+                        span.mismatches += 1;
+                        span.md.push_str(&format!("{}{}", md_run, r.to_ascii_uppercase()));
+                        md_run = 0;
+                        in_deletion = false;
+                    }
+                    CigarOp::Deletion => {
+                        span.deleted_bases += 1;
+                        if !in_deletion {
+                            span.md.push_str(&format!("{}^", md_run));
+                            md_run = 0;
+                            in_deletion = true;
+                        }
+                        span.md.push(r.to_ascii_uppercase());
+                    }
+                    CigarOp::Insertion => {
+                        span.inserted_bases += 1;
+                        // A deletion after an insertion is a new D operation with its own ^ block
+                        in_deletion = false;
+                    }
+                    CigarOp::SoftClip => {}
+                }
+                if *r != ALIGNMENT_GAP {
+                    span.reference_end += 1;
+                }
+                if *q != ALIGNMENT_GAP {
+                    span.query_end += 1;
+                }
+            }
+        }
+        span.md.push_str(&md_run.to_string());
+        span.push_op(CigarOp::SoftClip, query_length.saturating_sub(span.query_end));
+
+        span
+    }
+
+    fn push_op(&mut self, op: CigarOp, count: usize) {
+        if count == 0 {
+            return;
+        }
+        match self.cigar.last_mut() {
+            Some((last, n)) if *last == op => *n += count,
+            _ => self.cigar.push((op, count)),
+        }
+    }
+
+    // CIGAR string, e.g. "5S100=1X3I20="; "*" if nothing aligned
+    pub fn cigar_string(&self) -> String {
+        if self.cigar.is_empty() {
+            return "*".to_string();
+        }
+        self.cigar.iter().map(|(op, n)| format!("{}{}", n, op.symbol())).collect()
+    }
+
+    // Edit distance to the reference within the aligned region (SAM NM tag)
+    pub fn edit_distance(&self) -> usize {
+        self.mismatches + self.inserted_bases + self.deleted_bases
+    }
+
+    // Number of alignment columns in the aligned region
+    pub fn block_length(&self) -> usize {
+        self.matches + self.mismatches + self.inserted_bases + self.deleted_bases
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        self.matches + self.mismatches > 0
+    }
+
+    // MAPQ-like score: share of matching columns in the aligned region, scaled to 0-60
+    pub fn mapq(&self) -> u8 {
+        if self.is_mapped() {
+            (60.0 * self.matches as f64 / self.block_length() as f64).round() as u8
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn span_of_a_mismatch() {
+        let span = AlignmentSpan::from_alignment("AACGTA", "AAGGTA", 6);
+        assert_eq!(span.cigar_string(), "2=1X3=");
+        assert_eq!(span.md, "2C3");
+        assert_eq!(span.edit_distance(), 1);
+    }
+
+    #[test]
+    fn span_of_a_deletion_insertion_deletion() {
+        // The insertion splits the deletions into two D operations, each with its own ^ block
+        let span = AlignmentSpan::from_alignment("AAC_GAA", "AA_T_AA", 5);
+        assert_eq!(span.cigar_string(), "2=1D1I1D2=");
+        assert_eq!(span.md, "2^C0^G2");
+        assert_eq!(span.edit_distance(), 3);
+        assert_eq!((span.reference_start, span.reference_end), (0, 6));
+    }
+
+    #[test]
+    fn span_soft_clips_end_gaps() {
+        let span = AlignmentSpan::from_alignment("__ACGT__", "TTACGTCC", 8);
+        assert_eq!(span.cigar_string(), "2S4=2S");
+        assert_eq!(span.md, "4");
+        assert_eq!(span.edit_distance(), 0);
+        assert_eq!((span.query_start, span.query_end), (2, 6));
+    }
+}
@@ -0,0 +1,291 @@
+// NCBI genetic codes (translation tables), see
+// https://www.ncbi.nlm.nih.gov/Taxonomy/Utils/wprintgc.cgi
+//
+// Each table lists the amino acid of all 64 codons in NCBI order: first, second and third
+// base each running through T, C, A, G (TTT, TTC, TTA, TTG, TCT, ...). Codons are looked up
+// by index, so no map is built. Tables 27, 28 and 31 use some stop codons as sense codons
+// depending on context; they are listed with their sense meaning.
+
+#[derive(Debug)]
+pub struct GeneticCode {
+    pub id: u8,
+    pub name: &'static str,
+    amino_acids: &'static str,
+    start_codons: &'static [&'static str],
+}
+
+impl GeneticCode {
+    // Amino acid of a DNA or RNA codon (case-insensitive), '*' for stop codons;
+    // None if the codon is not three of A, C, G, T/U
+    pub fn translate(&self, codon: &str) -> Option<char> {
+        codon_index(codon).map(|index| self.amino_acids.as_bytes()[index] as char)
+    }
+
+    // Amino acid of a codon that may contain IUPAC ambiguity codes (R, Y, S, W, K, M, B, D,
+    // H, V, N): the amino acid all possible codons agree on, e.g. 'A' for GCN, or 'X' when
+    // they disagree or the codon is not three nucleotide letters. '*' if all are stops.
+    pub fn translate_ambiguous(&self, codon: &str) -> char {
+        let bytes = codon.as_bytes();
+        if bytes.len() != 3 {
+            return 'X';
+        }
+        let mut amino_acid = None;
+        for first in expand_base(bytes[0]) {
+            for second in expand_base(bytes[1]) {
+                for third in expand_base(bytes[2]) {
+                    let index = (base_value(*first) * 4 + base_value(*second)) * 4 + base_value(*third);
+                    let candidate = self.amino_acids.as_bytes()[index] as char;
+                    match amino_acid {
+                        None => amino_acid = Some(candidate),
+                        Some(previous) if previous != candidate => return 'X',
+                        Some(_) => {}
+                    }
+                }
+            }
+        }
+        amino_acid.unwrap_or('X')
+    }
+
+    pub fn is_stop(&self, codon: &str) -> bool {
+        self.translate(codon) == Some('*')
+    }
+
+    // Whether the codon can start translation in this code
+    pub fn is_start(&self, codon: &str) -> bool {
+        let codon = codon.to_ascii_uppercase().replace('U', "T");
+        self.start_codons.contains(&codon.as_str())
+    }
+
+    pub fn start_codons(&self) -> &'static [&'static str] {
+        self.start_codons
+    }
+}
+
+pub static GENETIC_CODES: [GeneticCode; 27] = [
+    GeneticCode {
+        id: 1,
+        name: "Standard",
+        amino_acids: "FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        start_codons: &["TTG", "CTG", "ATG"],
+    },
+    GeneticCode {
+        id: 2,
+        name: "Vertebrate Mitochondrial",
+        amino_acids: "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG",
+        start_codons: &["ATT", "ATC", "ATA", "ATG", "GTG"],
+    },
+    GeneticCode {
+        id: 3,
+        name: "Yeast Mitochondrial",
+        amino_acids: "FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        start_codons: &["ATA", "ATG", "GTG"],
+    },
+    GeneticCode {
+        id: 4,
+        name: "Mold, Protozoan, and Coelenterate Mitochondrial; Mycoplasma; Spiroplasma",
+        amino_acids: "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        start_codons: &["TTA", "TTG", "CTG", "ATT", "ATC", "ATA", "ATG", "GTG"],
+    },
+    GeneticCode {
+        id: 5,
+        name: "Invertebrate Mitochondrial",
+        amino_acids: "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSSSVVVVAAAADDEEGGGG",
+        start_codons: &["TTG", "ATT", "ATC", "ATA", "ATG", "GTG"],
+    },
+    GeneticCode {
+        id: 6,
+        name: "Ciliate, Dasycladacean and Hexamita Nuclear",
+        amino_acids: "FFLLSSSSYYQQCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        start_codons: &["ATG"],
+    },
+    GeneticCode {
+        id: 9,
+        name: "Echinoderm and Flatworm Mitochondrial",
+        amino_acids: "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+        start_codons: &["ATG", "GTG"],
+    },
+    GeneticCode {
+        id: 10,
+        name: "Euplotid Nuclear",
+        amino_acids: "FFLLSSSSYY**CCCWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        start_codons: &["ATG"],
+    },
+    GeneticCode {
+        id: 11,
+        name: "Bacterial, Archaeal and Plant Plastid",
+        amino_acids: "FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        start_codons: &["TTG", "CTG", "ATT", "ATC", "ATA", "ATG", "GTG"],
+    },
+    GeneticCode {
+        id: 12,
+        name: "Alternative Yeast Nuclear",
+        amino_acids: "FFLLSSSSYY**CC*WLLLSPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        start_codons: &["CTG", "ATG"],
+    },
+    GeneticCode {
+        id: 13,
+        name: "Ascidian Mitochondrial",
+        amino_acids: "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSGGVVVVAAAADDEEGGGG",
+        start_codons: &["TTG", "ATA", "ATG", "GTG"],
+    },
+    GeneticCode {
+        id: 14,
+        name: "Alternative Flatworm Mitochondrial",
+        amino_acids: "FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+        start_codons: &["ATG"],
+    },
+    GeneticCode {
+        id: 15,
+        name: "Blepharisma Macronuclear",
+        amino_acids: "FFLLSSSSYY*QCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        start_codons: &["ATG"],
+    },
+    GeneticCode {
+        id: 16,
+        name: "Chlorophycean Mitochondrial",
+        amino_acids: "FFLLSSSSYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        start_codons: &["ATG"],
+    },
+    GeneticCode {
+        id: 21,
+        name: "Trematode Mitochondrial",
+        amino_acids: "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+        start_codons: &["ATG", "GTG"],
+    },
+    GeneticCode {
+        id: 22,
+        name: "Scenedesmus obliquus Mitochondrial",
+        amino_acids: "FFLLSS*SYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        start_codons: &["ATG"],
+    },
+    GeneticCode {
+        id: 23,
+        name: "Thraustochytrium Mitochondrial",
+        amino_acids: "FF*LSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        start_codons: &["ATT", "ATG", "GTG"],
+    },
+    GeneticCode {
+        id: 24,
+        name: "Rhabdopleuridae Mitochondrial",
+        amino_acids: "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG",
+        start_codons: &["TTG", "CTG", "ATG", "GTG"],
+    },
+    GeneticCode {
+        id: 25,
+        name: "Candidate Division SR1 and Gracilibacteria",
+        amino_acids: "FFLLSSSSYY**CCGWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        start_codons: &["TTG", "ATG", "GTG"],
+    },
+    GeneticCode {
+        id: 26,
+        name: "Pachysolen tannophilus Nuclear",
+        amino_acids: "FFLLSSSSYY**CC*WLLLAPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        start_codons: &["CTG", "ATG"],
+    },
+    GeneticCode {
+        id: 27,
+        name: "Karyorelict Nuclear",
+        amino_acids: "FFLLSSSSYYQQCCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        start_codons: &["ATG"],
+    },
+    GeneticCode {
+        id: 28,
+        name: "Condylostoma Nuclear",
+        amino_acids: "FFLLSSSSYYQQCCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        start_codons: &["ATG"],
+    },
+    GeneticCode {
+        id: 29,
+        name: "Mesodinium Nuclear",
+        amino_acids: "FFLLSSSSYYYYCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        start_codons: &["ATG"],
+    },
+    GeneticCode {
+        id: 30,
+        name: "Peritrich Nuclear",
+        amino_acids: "FFLLSSSSYYEECC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        start_codons: &["ATG"],
+    },
+    GeneticCode {
+        id: 31,
+        name: "Blastocrithidia Nuclear",
+        amino_acids: "FFLLSSSSYYEECCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        start_codons: &["ATG"],
+    },
+    GeneticCode {
+        id: 32,
+        name: "Balanophoraceae Plastid",
+        amino_acids: "FFLLSSSSYY*WCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        start_codons: &["TTG", "CTG", "ATT", "ATC", "ATA", "ATG", "GTG"],
+    },
+    GeneticCode {
+        id: 33,
+        name: "Cephalodiscidae Mitochondrial UAA-Tyr",
+        amino_acids: "FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG",
+        start_codons: &["TTG", "CTG", "ATG", "GTG"],
+    },
+];
+
+// Look up a table by its NCBI id
+pub fn genetic_code(id: u8) -> Result<&'static GeneticCode, String> {
+    GENETIC_CODES.iter().find(|code| code.id == id).ok_or_else(|| {
+        let ids: Vec<String> = GENETIC_CODES.iter().map(|code| code.id.to_string()).collect();
+        format!("Unknown genetic code {}. NCBI tables: {}.", id, ids.join(", "))
+    })
+}
+
+// Table 1, the default everywhere
+pub fn standard_code() -> &'static GeneticCode {
+    &GENETIC_CODES[0]
+}
+
+// Index of a codon in NCBI order (T=0, C=1, A=2, G=3 per base)
+fn codon_index(codon: &str) -> Option<usize> {
+    let bytes = codon.as_bytes();
+    if bytes.len() != 3 {
+        return None;
+    }
+    let mut index = 0;
+    for base in bytes {
+        let value = match base.to_ascii_uppercase() {
+            b'T' | b'U' => 0,
+            b'C' => 1,
+            b'A' => 2,
+            b'G' => 3,
+            _ => return None,
+        };
+        index = index * 4 + value;
+    }
+    Some(index)
+}
+
+fn base_value(base: u8) -> usize {
+    match base {
+        b'T' => 0,
+        b'C' => 1,
+        b'A' => 2,
+        _ => 3,
+    }
+}
+
+// Nucleotides an IUPAC code stands for; empty for anything that is not a nucleotide code
+fn expand_base(base: u8) -> &'static [u8] {
+    match base.to_ascii_uppercase() {
+        b'A' => b"A",
+        b'C' => b"C",
+        b'G' => b"G",
+        b'T' | b'U' => b"T",
+        b'R' => b"AG",
+        b'Y' => b"CT",
+        b'S' => b"CG",
+        b'W' => b"AT",
+        b'K' => b"GT",
+        b'M' => b"AC",
+        b'B' => b"CGT",
+        b'D' => b"AGT",
+        b'H' => b"ACT",
+        b'V' => b"ACG",
+        b'N' => b"ACGT",
+        _ => b"",
+    }
+}
@@ -0,0 +1,204 @@
+use std::error::Error;
+use std::io::Write;
+
+use crate::formats::AlignmentInput;
+use crate::genetic_code::GeneticCode;
+use crate::utils::{create_output, translate_in_frame};
+use crate::variants::{extract_variants, right_normalize, Variant, VariantKind};
+
+// Stop codon / termination residue
+const STOP: char = '*';
+
+// Write every difference between query and reference in HGVS notation, one per line.
+// Nucleotide references are taken as a coding sequence starting at c.1, and each
+// variant gets the c. description and its predicted effect on the protein (p.).
+// Amino acid alignments only get p. descriptions. Residues use one-letter codes.
+// Translation uses the given NCBI genetic code.
+pub fn write_hgvs(
+    output_file: &str,
+    input: &AlignmentInput,
+    sequence_type: &str,
+    genetic_code: &GeneticCode,
+    reference_aligned: &str,
+    query_aligned: &str,
+) -> Result<(), Box<dyn Error>> {
+    let reference: Vec<char> = input.reference_sequence.to_uppercase().chars().collect();
+    let coding = sequence_type == "nucleotide";
+    let reference_protein: Vec<char> = if coding {
+        translate_in_frame(input.reference_sequence, genetic_code).chars().collect()
+    } else {
+        reference.clone()
+    };
+
+    let mut file = create_output(output_file)?;
+    writeln!(file, "#reference\tquery\ttype\tcoding\tprotein")?;
+    for variant in extract_variants(reference_aligned, query_aligned) {
+        let variant = right_normalize(&variant, &reference);
+        let (coding_notation, protein_notation) = if coding {
+            (
+                coding_notation(&variant, &reference),
+                protein_consequence(&variant, &reference, &reference_protein, genetic_code),
+            )
+        } else {
+            (".".to_string(), protein_notation(&variant, &reference_protein))
+        };
+        writeln!(
+            file,
+            "{}\t{}\t{}\t{}\t{}",
+            input.reference_id(),
+            input.query_id(),
+            variant.kind.name(),
+            coding_notation,
+            protein_notation
+        )?;
+    }
+    Ok(())
+}
+
+// c. description of a right-normalised nucleotide variant, e.g. c.76A>G, c.76_78del,
+// c.76dup, c.76_77insG or c.2956_2958delinsCCT
+pub fn coding_notation(variant: &Variant, reference: &[char]) -> String {
+    let first = variant.start + 1;
+    let last = variant.end();
+    match variant.kind {
+        VariantKind::Snv => format!("c.{}{}>{}", first, variant.reference, variant.alternate),
+        VariantKind::Mnv => format!("c.{}_{}delins{}", first, last, variant.alternate),
+        VariantKind::Deletion if first == last => format!("c.{}del", first),
+        VariantKind::Deletion => format!("c.{}_{}del", first, last),
+        VariantKind::Insertion => match duplicated_range(variant, reference) {
+            Some((dup_first, dup_last)) if dup_first == dup_last => format!("c.{}dup", dup_first),
+            Some((dup_first, dup_last)) => format!("c.{}_{}dup", dup_first, dup_last),
+            None => format!("c.{}_{}ins{}", variant.start, variant.start + 1, variant.alternate),
+        },
+    }
+}
+
+// p. description of a right-normalised amino acid variant, e.g. p.K986P, p.Q493*,
+// p.K986_V987del, p.K986dup, p.K986_V987insG or p.K986_V987delinsPP
+pub fn protein_notation(variant: &Variant, protein: &[char]) -> String {
+    let residue = |index: usize| match protein.get(index) {
+        Some(amino_acid) => format!("{}{}", amino_acid, index + 1),
+        None => (index + 1).to_string(),
+    };
+    let first = residue(variant.start);
+    let last = residue(variant.end().saturating_sub(1));
+    match variant.kind {
+        VariantKind::Snv if variant.start == 0 && protein.first() == Some(&'M') => "p.M1?".to_string(),
+        VariantKind::Snv => format!("p.{}{}", first, variant.alternate),
+        VariantKind::Mnv => format!("p.{}_{}delins{}", first, last, variant.alternate),
+        VariantKind::Deletion if variant.reference.chars().count() == 1 => format!("p.{}del", first),
+        VariantKind::Deletion => format!("p.{}_{}del", first, last),
+        VariantKind::Insertion => match duplicated_range(variant, protein) {
+            Some((dup_first, dup_last)) if dup_first == dup_last => format!("p.{}dup", residue(dup_first - 1)),
+            Some((dup_first, dup_last)) => format!("p.{}_{}dup", residue(dup_first - 1), residue(dup_last - 1)),
+            None => format!(
+                "p.{}_{}ins{}",
+                residue(variant.start.saturating_sub(1)),
+                residue(variant.start),
+                variant.alternate
+            ),
+        },
+    }
+}
+
+// Predicted p. effect of a nucleotide variant in the coding reference: translate the
+// reference with only this variant applied and describe how the protein changes
+pub fn protein_consequence(
+    variant: &Variant,
+    reference: &[char],
+    reference_protein: &[char],
+    genetic_code: &GeneticCode,
+) -> String {
+    let mut mutant: String = reference[..variant.start].iter().collect();
+    mutant.push_str(&variant.alternate);
+    mutant.extend(&reference[variant.end()..]);
+    let mutant_protein: Vec<char> = translate_in_frame(&mutant, genetic_code).chars().collect();
+
+    // First changed amino acid
+    let changed = reference_protein
+        .iter()
+        .zip(&mutant_protein)
+        .take_while(|(r, m)| r == m)
+        .count();
+    if changed == reference_protein.len() && changed == mutant_protein.len() {
+        // Synonymous: name the codon the variant starts in
+        let codon = (variant.start / 3).min(reference_protein.len().saturating_sub(1));
+        return match reference_protein.get(codon) {
+            Some(amino_acid) => format!("p.{}{}=", amino_acid, codon + 1),
+            None => "p.=".to_string(),
+        };
+    }
+    let residue = |index: usize| format!("{}{}", reference_protein.get(index).copied().unwrap_or('X'), index + 1);
+    let new_amino_acid = mutant_protein.get(changed).copied();
+
+    if changed == 0 && reference_protein.first() == Some(&'M') {
+        return "p.M1?".to_string();
+    }
+    if reference_protein.get(changed) == Some(&STOP) {
+        // Stop lost: the protein runs on to the next stop, if any
+        return format!(
+            "p.*{}{}ext*{}",
+            changed + 1,
+            new_amino_acid.unwrap_or('X'),
+            stop_distance(mutant_protein.get(changed + 1..).unwrap_or(&[]))
+        );
+    }
+    if new_amino_acid == Some(STOP) {
+        return format!("p.{}*", residue(changed));
+    }
+
+    let length_change = variant.alternate.chars().count() as isize - variant.reference.chars().count() as isize;
+    if length_change % 3 != 0 {
+        return format!(
+            "p.{}{}fs*{}",
+            residue(changed),
+            new_amino_acid.unwrap_or('X'),
+            stop_distance(&mutant_protein[changed..])
+        );
+    }
+
+    // In-frame change: trim the shared end of both proteins and describe what is left
+    let shared_end = reference_protein[changed..]
+        .iter()
+        .rev()
+        .zip(mutant_protein[changed..].iter().rev())
+        .take_while(|(r, m)| r == m)
+        .count();
+    let removed: String = reference_protein[changed..reference_protein.len() - shared_end].iter().collect();
+    let added: String = mutant_protein[changed..mutant_protein.len() - shared_end].iter().collect();
+    let kind = match (removed.chars().count(), added.chars().count()) {
+        (0, _) => VariantKind::Insertion,
+        (_, 0) => VariantKind::Deletion,
+        (1, 1) => VariantKind::Snv,
+        _ => VariantKind::Mnv,
+    };
+    let protein_variant = Variant {
+        kind,
+        start: changed,
+        reference: removed,
+        alternate: added,
+    };
+    protein_notation(&right_normalize(&protein_variant, reference_protein), reference_protein)
+}
+
+// 1-based range an insertion duplicates when it repeats the residues just before it
+fn duplicated_range(variant: &Variant, reference: &[char]) -> Option<(usize, usize)> {
+    let inserted: Vec<char> = variant.alternate.chars().collect();
+    if inserted.is_empty() || inserted.len() > variant.start {
+        return None;
+    }
+    let before = &reference[variant.start - inserted.len()..variant.start];
+    if before.iter().zip(&inserted).all(|(r, i)| r.eq_ignore_ascii_case(i)) {
+        Some((variant.start - inserted.len() + 1, variant.start))
+    } else {
+        None
+    }
+}
+
+// HGVS stop position: codons up to and including the first stop, "?" if there is none
+fn stop_distance(protein: &[char]) -> String {
+    match protein.iter().position(|amino_acid| *amino_acid == STOP) {
+        Some(index) => (index + 1).to_string(),
+        None => "?".to_string(),
+    }
+}
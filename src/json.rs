@@ -0,0 +1,96 @@
+use std::error::Error;
+use std::io::Write;
+
+use crate::formats::{record_description, record_id, AlignmentInput, AlignmentParameters, AlignmentSpan, AlignmentStats, ALIGNMENT_GAP};
+use crate::utils::create_output;
+
+// Version of the JSON document layout. Bump the major version when fields are removed
+// or change meaning, the minor version when fields are added.
+pub const JSON_SCHEMA_VERSION: &str = "1.0";
+
+// Write the alignment and its metadata as one JSON document (schema in the README)
+pub fn write_json(
+    output_file: &str,
+    input: &AlignmentInput,
+    parameters: &AlignmentParameters,
+    reference_aligned: &str,
+    alignment_visualization: &str,
+    query_aligned: &str,
+    score: &str,
+) -> Result<(), Box<dyn Error>> {
+    let stats = AlignmentStats::from_alignment(reference_aligned, query_aligned);
+    let span = AlignmentSpan::from_alignment(reference_aligned, query_aligned, input.query_sequence.chars().count());
+    let identity = if stats.length == 0 { 0.0 } else { stats.matches as f64 / stats.length as f64 };
+
+    let mut file = create_output(output_file)?;
+    writeln!(file, "{{")?;
+    writeln!(file, "  \"schema_version\": {},", json_string(JSON_SCHEMA_VERSION))?;
+    writeln!(
+        file,
+        "  \"program\": {{\"name\": \"aligner\", \"version\": {}}},",
+        json_string(env!("CARGO_PKG_VERSION"))
+    )?;
+    writeln!(file, "  \"score\": {},", score)?;
+    writeln!(file, "  \"parameters\": {{")?;
+    writeln!(file, "    \"match_score\": {},", parameters.match_score)?;
+    writeln!(file, "    \"mismatch_penalty\": {},", parameters.mismatch_penalty)?;
+    writeln!(file, "    \"gap_penalty\": {},", parameters.gap_penalty)?;
+    writeln!(file, "    \"unpenalized_end_gaps\": {},", parameters.unpenalized_end_gaps)?;
+    writeln!(file, "    \"sequence_type\": {},", json_string(parameters.sequence_type))?;
+    writeln!(file, "    \"score_type\": {}", json_string(parameters.score_type))?;
+    writeln!(file, "  }},")?;
+    for (name, header, sequence) in [
+        ("reference", input.reference_header, input.reference_sequence),
+        ("query", input.query_header, input.query_sequence),
+    ] {
+        writeln!(
+            file,
+            "  \"{}\": {{\"id\": {}, \"description\": {}, \"length\": {}}},",
+            name,
+            json_string(record_id(header)),
+            json_string(record_description(header)),
+            sequence.chars().count()
+        )?;
+    }
+    writeln!(file, "  \"alignment\": {{")?;
+    writeln!(file, "    \"gap_character\": {},", json_string(&ALIGNMENT_GAP.to_string()))?;
+    writeln!(file, "    \"reference\": {},", json_string(reference_aligned))?;
+    writeln!(file, "    \"markup\": {},", json_string(alignment_visualization))?;
+    writeln!(file, "    \"query\": {}", json_string(query_aligned))?;
+    writeln!(file, "  }},")?;
+    writeln!(file, "  \"cigar\": {},", json_string(&span.cigar_string()))?;
+    writeln!(file, "  \"coordinates\": {{")?;
+    writeln!(file, "    \"reference_start\": {},", span.reference_start)?;
+    writeln!(file, "    \"reference_end\": {},", span.reference_end)?;
+    writeln!(file, "    \"query_start\": {},", span.query_start)?;
+    writeln!(file, "    \"query_end\": {}", span.query_end)?;
+    writeln!(file, "  }},")?;
+    writeln!(file, "  \"statistics\": {{")?;
+    writeln!(file, "    \"length\": {},", stats.length)?;
+    writeln!(file, "    \"matches\": {},", stats.matches)?;
+    writeln!(file, "    \"mismatches\": {},", stats.mismatches)?;
+    writeln!(file, "    \"gaps\": {},", stats.gaps)?;
+    writeln!(file, "    \"identity\": {:.6}", identity)?;
+    writeln!(file, "  }}")?;
+    writeln!(file, "}}")?;
+    Ok(())
+}
+
+// Quote and escape a string for JSON
+pub fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,33 @@
+// The aligner as a library: Needleman-Wunsch alignment with its scoring, FASTA/FASTQ reading,
+// translation and the output formats. The aligner and analysis binaries are built on it.
+pub mod alignment;
+pub mod alignment_file;
+pub mod codon_usage;
+pub mod codons;
+pub mod dnds;
+pub mod dp;
+pub mod formats;
+pub mod genetic_code;
+pub mod hgvs;
+pub mod json;
+pub mod modified_bases;
+pub mod msa;
+pub mod orf;
+pub mod paf;
+pub mod profile;
+pub mod report;
+pub mod sam;
+pub mod score;
+pub mod utils;
+pub mod variants;
+pub mod vcf;
+
+pub use crate::alignment::{needleman_wunsch, AlignmentResult, Scoring};
+pub use crate::alignment_file::AlignmentFile;
+pub use crate::genetic_code::{genetic_code, standard_code, GeneticCode};
+pub use crate::modified_bases::ModifiedBaseAlphabet;
+pub use crate::score::{Score, ScoreOverflow};
+pub use crate::utils::{
+    open_fasta, read_fasta_records, read_fasta_sequence, select_fasta_record, translate, translate_sequence,
+    FastaRecord, TranslationOptions,
+};
@@ -0,0 +1,124 @@
+// Modified nucleosides written as their own symbols in annotated mRNA sequences, e.g. Ψ for
+// the (N1-methyl)pseudouridine of the Pfizer and Moderna vaccines. Sequences are read with
+// each modified base replaced by the canonical base it stands for, so translation, GC content
+// and alignment scoring all treat it as that base.
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModifiedBase {
+    pub symbol: char,
+    // One of A, C, G, U
+    pub canonical: char,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModifiedBaseAlphabet {
+    bases: Vec<ModifiedBase>,
+}
+
+// Number of times each modified symbol occurred in a sequence
+pub type ModifiedBaseCounts = BTreeMap<char, usize>;
+
+impl Default for ModifiedBaseAlphabet {
+    // Ψ/ψ, the symbol annotated vaccine sequences use for pseudouridine and m1Ψ alike
+    fn default() -> Self {
+        let pseudouridine = |symbol| ModifiedBase {
+            symbol,
+            canonical: 'U',
+            name: "pseudouridine / N1-methylpseudouridine".to_string(),
+        };
+        ModifiedBaseAlphabet {
+            bases: vec![pseudouridine('Ψ'), pseudouridine('ψ')],
+        }
+    }
+}
+
+impl ModifiedBaseAlphabet {
+    // Default alphabet extended (or overridden symbol by symbol) by a tab-separated file of
+    // `symbol  canonical base  name` lines, e.g. "1\tU\tm1Ψ" or "5\tC\tm5C". Blank lines and
+    // lines starting with '#' are skipped.
+    pub fn read(file_path: &str) -> Result<ModifiedBaseAlphabet, Box<dyn Error>> {
+        let text = fs::read_to_string(file_path)
+            .map_err(|e| format!("Cannot read modified base alphabet '{}': {}", file_path, e))?;
+        let mut alphabet = ModifiedBaseAlphabet::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| format!("'{}' line {}: {}", file_path, number + 1, message);
+            let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+            if fields.len() < 2 {
+                return Err(error("expected 'symbol<TAB>canonical base<TAB>name'.").into());
+            }
+            let mut symbol = fields[0].chars();
+            let symbol = match (symbol.next(), symbol.next()) {
+                (Some(symbol), None) => symbol,
+                _ => return Err(error("the symbol must be a single character.").into()),
+            };
+            let canonical = match fields[1].to_ascii_uppercase().as_str() {
+                "A" => 'A',
+                "C" => 'C',
+                "G" => 'G',
+                "U" | "T" => 'U',
+                _ => return Err(error("the canonical base must be A, C, G or U.").into()),
+            };
+            let name = fields.get(2).copied().filter(|name| !name.is_empty()).unwrap_or(fields[0]);
+            alphabet.insert(ModifiedBase { symbol, canonical, name: name.to_string() });
+        }
+        Ok(alphabet)
+    }
+
+    fn insert(&mut self, base: ModifiedBase) {
+        self.bases.retain(|existing| existing.symbol != base.symbol);
+        self.bases.push(base);
+    }
+
+    pub fn get(&self, symbol: char) -> Option<&ModifiedBase> {
+        self.bases.iter().find(|base| base.symbol == symbol)
+    }
+
+    pub fn contains(&self, symbol: char) -> bool {
+        self.get(symbol).is_some()
+    }
+
+    // Replace every modified base by its canonical base and count them. Uridine analogues
+    // become U only in sequences that use U elsewhere, and T otherwise, so that they match
+    // the T of DNA-style references such as the spike gene.
+    pub fn canonicalize(&self, sequence: &str) -> (String, ModifiedBaseCounts) {
+        let mut counts = ModifiedBaseCounts::new();
+        if !sequence.chars().any(|c| self.contains(c)) {
+            return (sequence.to_string(), counts);
+        }
+        let dna = !sequence.chars().any(|c| c.eq_ignore_ascii_case(&'U') && !self.contains(c));
+        let canonical = sequence
+            .chars()
+            .map(|c| match self.get(c) {
+                Some(base) => {
+                    *counts.entry(c).or_insert(0) += 1;
+                    if base.canonical == 'U' && dna {
+                        'T'
+                    } else {
+                        base.canonical
+                    }
+                }
+                None => c,
+            })
+            .collect();
+        (canonical, counts)
+    }
+
+    // One "count symbol (name, canonical base)" entry per modified base found, for messages
+    pub fn describe(&self, counts: &ModifiedBaseCounts) -> Vec<String> {
+        counts
+            .iter()
+            .map(|(symbol, count)| match self.get(*symbol) {
+                Some(base) => format!("{} {} ({}, {})", count, symbol, base.name, base.canonical),
+                None => format!("{} {}", count, symbol),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,310 @@
+use std::error::Error;
+use std::io::Write;
+
+use crate::formats::{record_description, record_id, ALIGNMENT_GAP};
+use crate::alignment::{needleman_wunsch, Scoring};
+use crate::profile::{align_profiles, Profile, ProfileScoring, GAP};
+use crate::score::ScoreOverflow;
+use crate::utils::{create_output, FastaRecord};
+
+// Residues per line in Clustal output
+const CLUSTAL_LINE_WIDTH: usize = 60;
+
+// Rooted binary guide tree over the input sequences (leaves are record indices)
+#[derive(Debug, Clone, PartialEq)]
+pub enum GuideTree {
+    Leaf(usize),
+    Node(Box<GuideTree>, Box<GuideTree>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeMethod {
+    Upgma,
+    NeighborJoining,
+}
+
+// Holds a multiple alignment, rows in input order
+pub struct MultipleAlignment {
+    pub headers: Vec<String>,
+    pub rows: Vec<String>,
+}
+
+impl MultipleAlignment {
+    // Two-row alignment from a pairwise result, reference first, '_' gaps turned into GAP
+    pub fn from_pairwise(reference_header: &str, reference_aligned: &str, query_header: &str, query_aligned: &str) -> MultipleAlignment {
+        MultipleAlignment {
+            headers: vec![reference_header.to_string(), query_header.to_string()],
+            rows: vec![
+                reference_aligned.replace(ALIGNMENT_GAP, &GAP.to_string()),
+                query_aligned.replace(ALIGNMENT_GAP, &GAP.to_string()),
+            ],
+        }
+    }
+}
+
+// Progressive multiple alignment: pairwise distances, guide tree, then profile alignment
+// of the sequences in the order given by the tree
+pub fn progressive_alignment(
+    records: &[FastaRecord],
+    scoring: &ProfileScoring,
+    tree_method: TreeMethod,
+    unpenalized_end_gaps: bool,
+) -> Result<MultipleAlignment, Box<dyn Error>> {
+    if records.len() < 2 {
+        return Err("Multiple alignment needs at least two sequences.".into());
+    }
+
+    let distances = pairwise_distances(records, scoring, unpenalized_end_gaps)?;
+
+    let tree = match tree_method {
+        TreeMethod::Upgma => upgma(&distances),
+        TreeMethod::NeighborJoining => neighbor_joining(&distances),
+    };
+
+    let (profile, order) = align_along_tree(&tree, records, scoring, unpenalized_end_gaps)?;
+
+    // Put the rows back in input order
+    let mut headers = vec![String::new(); records.len()];
+    let mut rows = vec![String::new(); records.len()];
+    for (row, (index, header)) in profile.aligned_rows().into_iter().zip(order.into_iter().zip(profile.headers)) {
+        headers[index] = header;
+        rows[index] = row;
+    }
+
+    Ok(MultipleAlignment { headers, rows })
+}
+
+// Distance between every pair of sequences: the fraction of alignment columns that are not matches
+pub fn pairwise_distances(
+    records: &[FastaRecord],
+    scoring: &ProfileScoring,
+    unpenalized_end_gaps: bool,
+) -> Result<Vec<Vec<f64>>, ScoreOverflow> {
+    let pairwise_scoring = Scoring {
+        match_score: scoring.match_score,
+        mismatch_penalty: scoring.mismatch_penalty,
+        gap_penalty: scoring.gap_penalty,
+    };
+    let n = records.len();
+    let mut distances = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let alignment = needleman_wunsch(
+                &records[i].sequence,
+                &records[j].sequence,
+                &pairwise_scoring,
+                unpenalized_end_gaps,
+            )?;
+            let columns = alignment.alignment_visualization.chars().count();
+            let matches = alignment.alignment_visualization.matches('|').count();
+            let distance = if columns == 0 { 1.0 } else { 1.0 - matches as f64 / columns as f64 };
+            distances[i][j] = distance;
+            distances[j][i] = distance;
+        }
+    }
+    Ok(distances)
+}
+
+// UPGMA: repeatedly join the closest clusters, averaging distances weighted by cluster size
+pub fn upgma(distances: &[Vec<f64>]) -> GuideTree {
+    let mut clusters: Vec<(GuideTree, usize)> = (0..distances.len()).map(|i| (GuideTree::Leaf(i), 1)).collect();
+    let mut d: Vec<Vec<f64>> = distances.to_vec();
+
+    while clusters.len() > 1 {
+        let (a, b) = closest_pair(&d);
+        let (size_a, size_b) = (clusters[a].1 as f64, clusters[b].1 as f64);
+
+        // Distances from the joined cluster to every other cluster
+        let joined: Vec<f64> = (0..clusters.len())
+            .map(|k| (d[a][k] * size_a + d[b][k] * size_b) / (size_a + size_b))
+            .collect();
+
+        let (tree_b, count_b) = clusters.remove(b);
+        let (tree_a, count_a) = clusters.remove(a);
+        remove_from_matrix(&mut d, a, b, joined);
+        clusters.push((GuideTree::Node(Box::new(tree_a), Box::new(tree_b)), count_a + count_b));
+    }
+
+    clusters.pop().unwrap().0
+}
+
+// Neighbor joining; the unrooted result is rooted at the last join
+pub fn neighbor_joining(distances: &[Vec<f64>]) -> GuideTree {
+    let mut nodes: Vec<GuideTree> = (0..distances.len()).map(GuideTree::Leaf).collect();
+    let mut d: Vec<Vec<f64>> = distances.to_vec();
+
+    while nodes.len() > 2 {
+        let n = nodes.len();
+        let totals: Vec<f64> = d.iter().map(|row| row.iter().sum()).collect();
+
+        // Pick the pair minimising the Q criterion
+        let mut best = (0, 1);
+        let mut best_q = f64::INFINITY;
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let q = (n as f64 - 2.0) * d[i][j] - totals[i] - totals[j];
+                if q < best_q {
+                    best_q = q;
+                    best = (i, j);
+                }
+            }
+        }
+        let (a, b) = best;
+
+        let joined: Vec<f64> = (0..n).map(|k| (d[a][k] + d[b][k] - d[a][b]) / 2.0).collect();
+
+        let tree_b = nodes.remove(b);
+        let tree_a = nodes.remove(a);
+        remove_from_matrix(&mut d, a, b, joined);
+        nodes.push(GuideTree::Node(Box::new(tree_a), Box::new(tree_b)));
+    }
+
+    let second = nodes.pop().unwrap();
+    match nodes.pop() {
+        Some(first) => GuideTree::Node(Box::new(first), Box::new(second)),
+        None => second,
+    }
+}
+
+// Indices (a < b) of the smallest off-diagonal entry
+fn closest_pair(d: &[Vec<f64>]) -> (usize, usize) {
+    let mut best = (0, 1);
+    for i in 0..d.len() {
+        for j in (i + 1)..d.len() {
+            if d[i][j] < d[best.0][best.1] {
+                best = (i, j);
+            }
+        }
+    }
+    best
+}
+
+// Drop rows/columns a and b (a < b) and append the joined cluster's distances
+fn remove_from_matrix(d: &mut Vec<Vec<f64>>, a: usize, b: usize, joined: Vec<f64>) {
+    let mut kept: Vec<f64> = Vec::new();
+    for (k, distance) in joined.into_iter().enumerate() {
+        if k != a && k != b {
+            kept.push(distance);
+        }
+    }
+    d.remove(b);
+    d.remove(a);
+    for row in d.iter_mut() {
+        row.remove(b);
+        row.remove(a);
+    }
+    for (row, distance) in d.iter_mut().zip(&kept) {
+        row.push(*distance);
+    }
+    kept.push(0.0);
+    d.push(kept);
+}
+
+// Align the subtrees bottom-up. Returns the profile and the record index of each of its rows.
+fn align_along_tree(
+    tree: &GuideTree,
+    records: &[FastaRecord],
+    scoring: &ProfileScoring,
+    unpenalized_end_gaps: bool,
+) -> Result<(Profile, Vec<usize>), ScoreOverflow> {
+    match tree {
+        GuideTree::Leaf(index) => {
+            let record = &records[*index];
+            Ok((Profile::from_sequence(&record.header(), &record.sequence), vec![*index]))
+        }
+        GuideTree::Node(left, right) => {
+            let (left_profile, mut order) = align_along_tree(left, records, scoring, unpenalized_end_gaps)?;
+            let (right_profile, right_order) = align_along_tree(right, records, scoring, unpenalized_end_gaps)?;
+            let alignment = align_profiles(&left_profile, &right_profile, scoring, unpenalized_end_gaps)?;
+            order.extend(right_order);
+            Ok((alignment.profile, order))
+        }
+    }
+}
+
+// Write the multiple alignment as aligned FASTA
+pub fn write_msa_fasta(output_file: &str, alignment: &MultipleAlignment) -> Result<(), Box<dyn Error>> {
+    let mut file = create_output(output_file)?;
+    for (header, row) in alignment.headers.iter().zip(&alignment.rows) {
+        writeln!(file, ">{}", header.trim_start_matches('>'))?;
+        writeln!(file, "{}", row)?;
+    }
+    Ok(())
+}
+
+// Write the multiple alignment in Clustal format, '*' marking fully conserved columns
+pub fn write_msa_clustal(output_file: &str, alignment: &MultipleAlignment) -> Result<(), Box<dyn Error>> {
+    let mut file = create_output(output_file)?;
+    writeln!(file, "CLUSTAL W multiple sequence alignment")?;
+    writeln!(file)?;
+
+    let ids = record_ids(alignment);
+    let name_width = ids.iter().map(|id| id.len()).max().unwrap_or(0) + 4;
+    let rows: Vec<Vec<char>> = alignment.rows.iter().map(|row| row.chars().collect()).collect();
+    let width = rows.first().map_or(0, |row| row.len());
+
+    let mut start = 0;
+    while start < width {
+        let end = (start + CLUSTAL_LINE_WIDTH).min(width);
+        for (id, row) in ids.iter().zip(&rows) {
+            let block: String = row[start..end].iter().collect();
+            writeln!(file, "{:<width$}{}", id, block, width = name_width)?;
+        }
+        let conservation: String = (start..end)
+            .map(|col| {
+                let first = rows[0][col];
+                if first != GAP && rows.iter().all(|row| row[col] == first) {
+                    '*'
+                } else {
+                    ' '
+                }
+            })
+            .collect();
+        writeln!(file, "{:<width$}{}", "", conservation, width = name_width)?;
+        writeln!(file)?;
+        start = end;
+    }
+    Ok(())
+}
+
+// Write the multiple alignment in Stockholm format, one unwrapped block with the
+// full FASTA headers kept as #=GS DE lines
+pub fn write_msa_stockholm(output_file: &str, alignment: &MultipleAlignment) -> Result<(), Box<dyn Error>> {
+    let mut file = create_output(output_file)?;
+    writeln!(file, "# STOCKHOLM 1.0")?;
+
+    let ids = record_ids(alignment);
+    let name_width = ids.iter().map(|id| id.len()).max().unwrap_or(0) + 4;
+    for (id, header) in ids.iter().zip(&alignment.headers) {
+        let description = record_description(header);
+        if !description.is_empty() {
+            writeln!(file, "#=GS {:<width$}DE {}", id, description, width = name_width)?;
+        }
+    }
+    writeln!(file)?;
+    for (id, row) in ids.iter().zip(&alignment.rows) {
+        writeln!(file, "{:<width$}{}", id, row, width = name_width)?;
+    }
+    writeln!(file, "//")?;
+    Ok(())
+}
+
+// Row names for Clustal and Stockholm: the record ids, made unique with a suffix,
+// since both formats identify rows by name
+fn record_ids(alignment: &MultipleAlignment) -> Vec<String> {
+    let mut ids: Vec<String> = Vec::with_capacity(alignment.headers.len());
+    for header in &alignment.headers {
+        let id = match record_id(header) {
+            "" => "sequence",
+            id => id,
+        };
+        let mut unique = id.to_string();
+        let mut suffix = 1;
+        while ids.contains(&unique) {
+            suffix += 1;
+            unique = format!("{}_{}", id, suffix);
+        }
+        ids.push(unique);
+    }
+    ids
+}
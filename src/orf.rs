@@ -0,0 +1,188 @@
+// Six-frame translation and open reading frames (ORFs) of a nucleotide sequence
+use crate::genetic_code::GeneticCode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strand {
+    Forward,
+    Reverse,
+}
+
+impl Strand {
+    pub fn symbol(self) -> char {
+        match self {
+            Strand::Forward => '+',
+            Strand::Reverse => '-',
+        }
+    }
+}
+
+// Translation of one reading frame. `frame` is 1, 2 or 3: the offset of the first codon
+// plus one, counted from the 5' end of the strand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameTranslation {
+    pub strand: Strand,
+    pub frame: usize,
+    pub protein: String,
+}
+
+// One ORF, from a start codon up to and including the stop codon. `start` and `end` are
+// 1-based inclusive positions on the forward strand, so `start <= end` on both strands.
+// ORFs that run off the end of the sequence have no stop codon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Orf {
+    pub strand: Strand,
+    pub frame: usize,
+    pub start: usize,
+    pub end: usize,
+    pub has_stop: bool,
+    // Amino acids without the stop codon
+    pub protein: String,
+}
+
+impl Orf {
+    // Nucleotides of the ORF, stop codon included, read 5' to 3' on its own strand from the
+    // (forward strand) sequence it was found in
+    pub fn coding_sequence(&self, sequence: &str) -> String {
+        let forward: String = sequence.chars().skip(self.start - 1).take(self.end + 1 - self.start).collect();
+        match self.strand {
+            Strand::Forward => forward.to_ascii_uppercase().replace('U', "T"),
+            Strand::Reverse => reverse_complement(&forward),
+        }
+    }
+}
+
+// Reverse complement of a DNA or RNA sequence (RNA is returned as DNA); other characters
+// are kept as they are
+pub fn reverse_complement(sequence: &str) -> String {
+    sequence
+        .chars()
+        .rev()
+        .map(|base| match base.to_ascii_uppercase() {
+            'A' => 'T',
+            'C' => 'G',
+            'G' => 'C',
+            'T' | 'U' => 'A',
+            other => other,
+        })
+        .collect()
+}
+
+// Translate all three frames of both strands, stop codons included as '*'
+pub fn six_frame_translation(sequence: &str, genetic_code: &GeneticCode) -> Vec<FrameTranslation> {
+    let mut translations = Vec::with_capacity(6);
+    for (strand, strand_sequence) in strands(sequence) {
+        for frame in 1..=3 {
+            let protein = codons(&strand_sequence, frame - 1)
+                .map(|codon| genetic_code.translate_ambiguous(&codon))
+                .collect();
+            translations.push(FrameTranslation { strand, frame, protein });
+        }
+    }
+    translations
+}
+
+// All ORFs of at least `min_length` amino acids (stop codon not counted) in the six frames.
+// Each ORF opens at the first start codon after the previous stop in its frame, so nested
+// starts do not give extra ORFs. Only ATG opens an ORF unless `alternative_starts` is set,
+// in which case every start codon of the genetic code does and the first residue is M.
+// ORFs are sorted by forward-strand start position.
+pub fn find_orfs(sequence: &str, genetic_code: &GeneticCode, min_length: usize, alternative_starts: bool) -> Vec<Orf> {
+    let length = sequence.chars().count();
+    let is_start = |codon: &str| {
+        if alternative_starts {
+            genetic_code.is_start(codon)
+        } else {
+            codon.eq_ignore_ascii_case("ATG")
+        }
+    };
+
+    let mut orfs = Vec::new();
+    for (strand, strand_sequence) in strands(sequence) {
+        for frame in 1..=3 {
+            // Strand position of the open ORF's start codon, and its protein so far
+            let mut open: Option<(usize, String)> = None;
+            let mut position = frame - 1;
+            for codon in codons(&strand_sequence, frame - 1) {
+                let amino_acid = genetic_code.translate_ambiguous(&codon);
+                match open.as_mut() {
+                    Some((start, protein)) if amino_acid == '*' => {
+                        let (start, protein) = (*start, std::mem::take(protein));
+                        let location = StrandInterval { strand, frame, start, end: position + 3, has_stop: true };
+                        push_orf(&mut orfs, location, length, protein, min_length);
+                        open = None;
+                    }
+                    Some((_, protein)) => protein.push(amino_acid),
+                    None if is_start(&codon) => open = Some((position, "M".to_string())),
+                    None => {}
+                }
+                position += 3;
+            }
+            if let Some((start, protein)) = open {
+                let location = StrandInterval { strand, frame, start, end: position, has_stop: false };
+                push_orf(&mut orfs, location, length, protein, min_length);
+            }
+        }
+    }
+    orfs.sort_by_key(|orf| (orf.start, orf.end));
+    orfs
+}
+
+// Longest ORF by protein length; the first one found on a tie
+pub fn longest_orf(orfs: &[Orf]) -> Option<&Orf> {
+    orfs.iter().rev().max_by_key(|orf| orf.protein.len())
+}
+
+// Where an ORF was found: [start, end) in 0-based positions on its own strand
+struct StrandInterval {
+    strand: Strand,
+    frame: usize,
+    start: usize,
+    end: usize,
+    has_stop: bool,
+}
+
+// Convert the strand interval into forward-strand coordinates (`length` is the sequence
+// length) and keep the ORF if it is long enough
+fn push_orf(orfs: &mut Vec<Orf>, location: StrandInterval, length: usize, protein: String, min_length: usize) {
+    if protein.len() < min_length {
+        return;
+    }
+    let StrandInterval { strand, frame, start, end, has_stop } = location;
+    let (start, end) = match strand {
+        Strand::Forward => (start + 1, end),
+        Strand::Reverse => (length - end + 1, length - start),
+    };
+    orfs.push(Orf { strand, frame, start, end, has_stop, protein });
+}
+
+fn strands(sequence: &str) -> [(Strand, String); 2] {
+    [
+        (Strand::Forward, sequence.to_ascii_uppercase().replace('U', "T")),
+        (Strand::Reverse, reverse_complement(sequence)),
+    ]
+}
+
+// Complete codons of a strand from `offset`, counted in characters like the ORF coordinates
+// so that a multi-byte symbol is one base
+fn codons(sequence: &str, offset: usize) -> impl Iterator<Item = String> {
+    let bases: Vec<char> = sequence.chars().skip(offset).collect();
+    let codons: Vec<String> = bases.chunks_exact(3).map(|codon| codon.iter().collect()).collect();
+    codons.into_iter()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genetic_code::standard_code;
+
+    #[test]
+    fn find_orfs_counts_a_multi_byte_base_as_one_base() {
+        // Ψ shifts the ORF to frame 2 by one base, not by its two bytes
+        let orfs = find_orfs("ΨATGAAACCCTAAG", standard_code(), 1, false);
+        let orf = orfs.iter().find(|orf| orf.strand == Strand::Forward).unwrap();
+        assert_eq!((orf.frame, orf.start, orf.end), (2, 2, 13));
+        assert!(orf.has_stop);
+        assert_eq!(orf.protein, "MKP");
+        assert_eq!(orf.coding_sequence("ΨATGAAACCCTAAG"), "ATGAAACCCTAA");
+    }
+}
@@ -0,0 +1,48 @@
+use std::error::Error;
+use std::io::Write;
+
+use crate::formats::{AlignmentInput, AlignmentSpan, CigarOp};
+use crate::utils::create_output;
+
+// Write the alignment as one minimap2-style PAF line: query and target names, lengths and
+// 0-based half-open coordinates, strand, residue matches, block length and MAPQ, followed
+// by NM, AS and cg (CIGAR without soft clips) tags. AS is the score of the whole global
+// alignment, end gaps included, not a score of the reported block alone.
+pub fn write_paf(
+    output_file: &str,
+    input: &AlignmentInput,
+    reference_aligned: &str,
+    query_aligned: &str,
+    score: f64,
+) -> Result<(), Box<dyn Error>> {
+    let query_length = input.query_sequence.chars().count();
+    let span = AlignmentSpan::from_alignment(reference_aligned, query_aligned, query_length);
+
+    let cigar: String = span
+        .cigar
+        .iter()
+        .filter(|(op, _)| *op != CigarOp::SoftClip)
+        .map(|(op, n)| format!("{}{}", n, op.symbol()))
+        .collect();
+
+    let mut file = create_output(output_file)?;
+    writeln!(
+        file,
+        "{}\t{}\t{}\t{}\t+\t{}\t{}\t{}\t{}\t{}\t{}\t{}\ttp:A:P\tNM:i:{}\tAS:i:{}\tcg:Z:{}",
+        input.query_id(),
+        query_length,
+        span.query_start,
+        span.query_end,
+        input.reference_id(),
+        input.reference_sequence.chars().count(),
+        span.reference_start,
+        span.reference_end,
+        span.matches,
+        span.block_length(),
+        span.mapq(),
+        span.edit_distance(),
+        score.round() as i64,
+        cigar
+    )?;
+    Ok(())
+}
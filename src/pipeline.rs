@@ -0,0 +1,174 @@
+// Pipeline the analysis binary runs: inputs, the comparisons with their alignment jobs,
+// translation, statistics and the order of the steps, read from a TOML file. analysis.toml,
+// the default, is built into the binary.
+use aligner::alignment::Scoring;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+
+const DEFAULT_PIPELINE: &str = include_str!("../analysis.toml");
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pipeline {
+    pub modified_bases: Option<String>,
+    pub steps: Vec<Step>,
+    pub inputs: Vec<Input>,
+    pub scoring: Scoring<i32>,
+    pub translation: Translation,
+    pub statistics: Statistics,
+    pub codon_usage: Option<CodonUsageStep>,
+    #[serde(default)]
+    pub comparisons: Vec<Comparison>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    Orfs,
+    Comparisons,
+    ModifiedBases,
+    CodonOptimality,
+    CodonUsage,
+    Summary,
+}
+
+impl Step {
+    // Name as written in the pipeline file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Step::Orfs => "orfs",
+            Step::Comparisons => "comparisons",
+            Step::ModifiedBases => "modified_bases",
+            Step::CodonOptimality => "codon_optimality",
+            Step::CodonUsage => "codon_usage",
+            Step::Summary => "summary",
+        }
+    }
+}
+
+// A nucleotide sequence file and the file its translated CDS is saved to
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Input {
+    pub file: String,
+    pub amino_acid_file: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Translation {
+    pub genetic_code: u8,
+    pub min_orf_length: usize,
+    #[serde(default)]
+    pub alternative_starts: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Statistics {
+    pub dn_ds_replicates: usize,
+    pub dn_ds_seed: u64,
+    pub gc_significance: f64,
+}
+
+// Codon usage table and the files of the codon_optimality step
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CodonUsageStep {
+    pub table: String,
+    pub back_translate: String,
+    pub back_translation_output: String,
+    pub back_translation_header: String,
+    pub optimality_output: String,
+}
+
+// Two inputs compared with each other; alignment jobs without an output file are skipped
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Comparison {
+    pub name: String,
+    pub query: String,
+    pub reference: String,
+    pub penalized_output: Option<String>,
+    pub free_output: Option<String>,
+    pub amino_acid_output: Option<String>,
+    pub scoring: Option<Scoring<i32>>,
+}
+
+impl Pipeline {
+    pub fn read(file_path: &str) -> Result<Pipeline, Box<dyn Error>> {
+        let text = fs::read_to_string(file_path)
+            .map_err(|e| format!("Cannot read pipeline '{}': {}", file_path, e))?;
+        Pipeline::parse(&text).map_err(|e| format!("Pipeline '{}': {}", file_path, e).into())
+    }
+
+    // The bundled analysis.toml
+    pub fn default_pipeline() -> Result<Pipeline, Box<dyn Error>> {
+        Pipeline::parse(DEFAULT_PIPELINE).map_err(|e| format!("Default pipeline: {}", e).into())
+    }
+
+    fn parse(text: &str) -> Result<Pipeline, Box<dyn Error>> {
+        let pipeline: Pipeline = toml::from_str(text)?;
+        pipeline.validate()?;
+        Ok(pipeline)
+    }
+
+    pub fn has_step(&self, step: Step) -> bool {
+        self.steps.contains(&step)
+    }
+
+    pub fn input(&self, file: &str) -> Option<&Input> {
+        self.inputs.iter().find(|input| input.file == file)
+    }
+
+    // Scoring of a comparison's alignment jobs
+    pub fn scoring_for(&self, comparison: &Comparison) -> Scoring<i32> {
+        comparison.scoring.unwrap_or(self.scoring)
+    }
+
+    // Steps run once each and after the steps they need; comparisons and the back-translation
+    // refer to inputs
+    fn validate(&self) -> Result<(), String> {
+        let mut seen = BTreeSet::new();
+        for step in &self.steps {
+            if !seen.insert(*step) {
+                return Err(format!("step '{}' is listed twice.", step.name()));
+            }
+            let needs = match step {
+                Step::Orfs | Step::ModifiedBases => None,
+                Step::Comparisons | Step::CodonOptimality | Step::CodonUsage => Some(Step::Orfs),
+                Step::Summary => Some(Step::Comparisons),
+            };
+            if let Some(needed) = needs {
+                if !seen.contains(&needed) {
+                    return Err(format!("step '{}' has to come after '{}'.", step.name(), needed.name()));
+                }
+            }
+        }
+        let mut files = BTreeSet::new();
+        for input in &self.inputs {
+            if !files.insert(input.file.as_str()) {
+                return Err(format!("input '{}' is listed twice.", input.file));
+            }
+        }
+        for comparison in &self.comparisons {
+            for file in [&comparison.query, &comparison.reference] {
+                if self.input(file).is_none() {
+                    return Err(format!("comparison '{}' uses '{}', which is not an input.", comparison.name, file));
+                }
+            }
+        }
+        if self.has_step(Step::CodonOptimality) || self.has_step(Step::CodonUsage) {
+            let codon_usage = self
+                .codon_usage
+                .as_ref()
+                .ok_or("the codon_optimality and codon_usage steps need a [codon_usage] table.")?;
+            if self.has_step(Step::CodonOptimality) && self.input(&codon_usage.back_translate).is_none() {
+                return Err(format!("back_translate '{}' is not an input.", codon_usage.back_translate));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::dp::{align_dp, AlignOp};
+use crate::score::ScoreOverflow;
+use crate::utils::FastaRecord;
+
+// Gap character written into merged alignments
+pub const GAP: char = '-';
+
+// Weight of the background distribution mixed into log-odds column frequencies,
+// so that columns without shared residues still get a finite score
+const PSEUDOCOUNT_WEIGHT: f64 = 0.1;
+
+// '-' and '.' are the usual MSA gaps, '_' is what the aligner writes
+pub fn is_gap(c: char) -> bool {
+    c == '-' || c == '_' || c == '.'
+}
+
+// Residue and gap frequencies of one alignment column (all frequencies sum to 1)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileColumn {
+    pub residues: BTreeMap<char, f64>,
+    pub gap: f64,
+}
+
+impl ProfileColumn {
+    // Fraction of sequences with a residue in this column
+    pub fn occupancy(&self) -> f64 {
+        1.0 - self.gap
+    }
+}
+
+// Column profile of a multiple alignment. The aligned rows are kept so that
+// aligning two profiles also produces the merged multiple alignment.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<char>>,
+    pub columns: Vec<ProfileColumn>,
+}
+
+impl Profile {
+    // Profile of a single ungapped sequence
+    pub fn from_sequence(header: &str, sequence: &str) -> Profile {
+        Profile::from_rows(vec![header.to_string()], vec![sequence.chars().collect()])
+    }
+
+    // Profile of an existing multiple alignment given as aligned FASTA records
+    pub fn from_alignment(records: &[FastaRecord]) -> Result<Profile, Box<dyn Error>> {
+        if records.is_empty() {
+            return Err("Cannot build a profile from an empty alignment.".into());
+        }
+        let width = records[0].sequence.chars().count();
+        let mut headers = Vec::new();
+        let mut rows = Vec::new();
+        for record in records {
+            let row: Vec<char> = record.sequence.chars().collect();
+            if row.len() != width {
+                return Err(format!(
+                    "Aligned sequence '{}' has length {}, expected {} like the first record.",
+                    record.id,
+                    row.len(),
+                    width
+                )
+                .into());
+            }
+            headers.push(record.header());
+            rows.push(row);
+        }
+        let profile = Profile::from_rows(headers, rows);
+        if profile.is_empty() {
+            return Err("Cannot build a profile from an alignment without columns.".into());
+        }
+        Ok(profile)
+    }
+
+    fn from_rows(headers: Vec<String>, rows: Vec<Vec<char>>) -> Profile {
+        // Normalise gaps to GAP and residues to uppercase
+        let rows: Vec<Vec<char>> = rows
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|c| if is_gap(c) { GAP } else { c.to_ascii_uppercase() })
+                    .collect()
+            })
+            .collect();
+
+        let width = rows.first().map_or(0, |row| row.len());
+        let weight = 1.0 / rows.len() as f64;
+        let mut columns = Vec::with_capacity(width);
+        for col in 0..width {
+            let mut residues = BTreeMap::new();
+            let mut gap = 0.0;
+            for row in &rows {
+                if row[col] == GAP {
+                    gap += weight;
+                } else {
+                    *residues.entry(row[col]).or_insert(0.0) += weight;
+                }
+            }
+            columns.push(ProfileColumn { residues, gap });
+        }
+
+        Profile { headers, rows, columns }
+    }
+
+    // Number of columns
+    pub fn len(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    // Aligned rows as strings, in the order of `headers`
+    pub fn aligned_rows(&self) -> Vec<String> {
+        self.rows.iter().map(|row| row.iter().collect()).collect()
+    }
+
+    // Overall residue composition, used as background for log-odds scoring
+    fn residue_counts(&self, counts: &mut BTreeMap<char, f64>) {
+        for row in &self.rows {
+            for &c in row {
+                if c != GAP {
+                    *counts.entry(c).or_insert(0.0) += 1.0;
+                }
+            }
+        }
+    }
+}
+
+// How two profile columns are scored against each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnScoring {
+    // Expected match/mismatch/gap score of a residue pair drawn from each column
+    SumOfPairs,
+    // log2 of how much more often the columns share a residue than expected from the
+    // background composition. Only the gap penalty is used from the scoring parameters.
+    LogOdds,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ProfileScoring {
+    pub match_score: f64,
+    pub mismatch_penalty: f64,
+    pub gap_penalty: f64,
+    pub column_scoring: ColumnScoring,
+}
+
+// Holds profile alignment result
+pub struct ProfileAlignment {
+    pub score: f64,
+    pub profile: Profile,
+}
+
+// Align a sequence against a profile; the profile's rows come first in the result
+pub fn align_sequence_to_profile(
+    profile: &Profile,
+    header: &str,
+    sequence: &str,
+    scoring: &ProfileScoring,
+    unpenalized_end_gaps: bool,
+) -> Result<ProfileAlignment, ScoreOverflow> {
+    align_profiles(profile, &Profile::from_sequence(header, sequence), scoring, unpenalized_end_gaps)
+}
+
+// Align two profiles with the same DP as pairwise alignment, scoring columns instead of residues
+pub fn align_profiles(
+    profile1: &Profile,
+    profile2: &Profile,
+    scoring: &ProfileScoring,
+    unpenalized_end_gaps: bool,
+) -> Result<ProfileAlignment, ScoreOverflow> {
+    let background = match scoring.column_scoring {
+        ColumnScoring::SumOfPairs => BTreeMap::new(),
+        ColumnScoring::LogOdds => background_frequencies(profile1, profile2),
+    };
+
+    let (score, mut path) = align_dp(
+        profile1.len(),
+        profile2.len(),
+        |i, j| score_columns(&profile1.columns[i], &profile2.columns[j], scoring, &background),
+        |i| profile1.columns[i].occupancy() * scoring.gap_penalty,
+        |j| profile2.columns[j].occupancy() * scoring.gap_penalty,
+        unpenalized_end_gaps,
+    )?;
+
+    // Columns after the traceback start (free end gaps) still belong in the merged alignment
+    let used1 = path.iter().filter(|op| **op != AlignOp::Left).count();
+    let used2 = path.iter().filter(|op| **op != AlignOp::Up).count();
+    path.extend(std::iter::repeat_n(AlignOp::Up, profile1.len() - used1));
+    path.extend(std::iter::repeat_n(AlignOp::Left, profile2.len() - used2));
+
+    Ok(ProfileAlignment {
+        score,
+        profile: merge_profiles(profile1, profile2, &path),
+    })
+}
+
+// Expected score of aligning column c1 with column c2
+fn score_columns(
+    c1: &ProfileColumn,
+    c2: &ProfileColumn,
+    scoring: &ProfileScoring,
+    background: &BTreeMap<char, f64>,
+) -> f64 {
+    // Residue against gap costs a gap penalty, gap against gap is free
+    let gap_score = (c1.gap * c2.occupancy() + c1.occupancy() * c2.gap) * scoring.gap_penalty;
+
+    let residue_score = match scoring.column_scoring {
+        ColumnScoring::SumOfPairs => {
+            let mut total = 0.0;
+            for (a, fa) in &c1.residues {
+                for (b, fb) in &c2.residues {
+                    let pair = if a == b { scoring.match_score } else { scoring.mismatch_penalty };
+                    total += fa * fb * pair;
+                }
+            }
+            total
+        }
+        ColumnScoring::LogOdds => {
+            let (o1, o2) = (c1.occupancy(), c2.occupancy());
+            if o1 == 0.0 || o2 == 0.0 {
+                0.0
+            } else {
+                let mut odds = 0.0;
+                for (residue, bg) in background {
+                    let p1 = smoothed_frequency(c1, *residue, *bg);
+                    let p2 = smoothed_frequency(c2, *residue, *bg);
+                    odds += p1 * p2 / bg;
+                }
+                o1 * o2 * odds.log2()
+            }
+        }
+    };
+
+    residue_score + gap_score
+}
+
+// Residue frequency among the non-gap entries of a column, mixed with the background
+fn smoothed_frequency(column: &ProfileColumn, residue: char, background: f64) -> f64 {
+    let observed = column.residues.get(&residue).copied().unwrap_or(0.0) / column.occupancy();
+    (1.0 - PSEUDOCOUNT_WEIGHT) * observed + PSEUDOCOUNT_WEIGHT * background
+}
+
+// Residue composition of both profiles together
+fn background_frequencies(profile1: &Profile, profile2: &Profile) -> BTreeMap<char, f64> {
+    let mut counts = BTreeMap::new();
+    profile1.residue_counts(&mut counts);
+    profile2.residue_counts(&mut counts);
+    let total: f64 = counts.values().sum();
+    for count in counts.values_mut() {
+        *count /= total;
+    }
+    counts
+}
+
+// Build the multiple alignment described by a path over the columns of both profiles
+fn merge_profiles(profile1: &Profile, profile2: &Profile, path: &[AlignOp]) -> Profile {
+    let mut rows: Vec<Vec<char>> = vec![Vec::with_capacity(path.len()); profile1.rows.len() + profile2.rows.len()];
+    let split = profile1.rows.len();
+    let mut i = 0;
+    let mut j = 0;
+
+    for op in path {
+        let (take1, take2) = match op {
+            AlignOp::Diagonal => (true, true),
+            AlignOp::Up => (true, false),
+            AlignOp::Left => (false, true),
+        };
+        for (row, source) in rows[..split].iter_mut().zip(&profile1.rows) {
+            row.push(if take1 { source[i] } else { GAP });
+        }
+        for (row, source) in rows[split..].iter_mut().zip(&profile2.rows) {
+            row.push(if take2 { source[j] } else { GAP });
+        }
+        if take1 {
+            i += 1;
+        }
+        if take2 {
+            j += 1;
+        }
+    }
+
+    let headers = profile1.headers.iter().chain(&profile2.headers).cloned().collect();
+    Profile::from_rows(headers, rows)
+}
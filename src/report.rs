@@ -0,0 +1,136 @@
+use std::error::Error;
+use std::io::Write;
+
+use crate::formats::{AlignmentInput, AlignmentParameters, AlignmentStats, ALIGNMENT_GAP};
+use crate::utils::create_output;
+
+// Pairwise report modelled on EMBOSS needle: a commented header with the parameters and
+// identity/similarity/gap percentages, then the alignment wrapped at `width` columns with
+// the 1-based position of the first and last residue of each sequence on every line
+pub fn write_needle_report(
+    output_file: &str,
+    input: &AlignmentInput,
+    parameters: &AlignmentParameters,
+    reference_aligned: &str,
+    query_aligned: &str,
+    score: &str,
+    width: usize,
+) -> Result<(), Box<dyn Error>> {
+    let stats = AlignmentStats::from_alignment(reference_aligned, query_aligned);
+    let reference_id = input.reference_id();
+    let query_id = input.query_id();
+    let mut file = create_output(output_file)?;
+
+    writeln!(file, "########################################")?;
+    writeln!(file, "# Program: aligner {}", env!("CARGO_PKG_VERSION"))?;
+    writeln!(file, "# Align_format: needle")?;
+    writeln!(file, "########################################")?;
+    writeln!(file, "#")?;
+    writeln!(file, "#=======================================")?;
+    writeln!(file, "#")?;
+    writeln!(file, "# Aligned_sequences: 2")?;
+    writeln!(file, "# 1: {}", reference_id)?;
+    writeln!(file, "# 2: {}", query_id)?;
+    writeln!(file, "# Sequence_type: {}", parameters.sequence_type)?;
+    writeln!(file, "# Match_score: {}", parameters.match_score)?;
+    writeln!(file, "# Mismatch_penalty: {}", parameters.mismatch_penalty)?;
+    writeln!(file, "# Gap_penalty: {}", parameters.gap_penalty)?;
+    writeln!(
+        file,
+        "# End_gaps: {}",
+        if parameters.unpenalized_end_gaps { "unpenalized" } else { "penalized" }
+    )?;
+    writeln!(file, "#")?;
+    writeln!(file, "# Length: {}", stats.length)?;
+    // Without a substitution matrix, similar residues are identical residues
+    for (label, count) in [("Identity", stats.matches), ("Similarity", stats.matches), ("Gaps", stats.gaps)] {
+        let fraction = format!("{}/{}", count, stats.length);
+        writeln!(
+            file,
+            "# {:<11} {:>11} ({:>5.1}%)",
+            format!("{}:", label),
+            fraction,
+            stats.percent(count)
+        )?;
+    }
+    writeln!(file, "# Score: {}", score)?;
+    writeln!(file, "# Score_type: {}", parameters.score_type)?;
+    writeln!(file, "#")?;
+    writeln!(file, "#")?;
+    writeln!(file, "#=======================================")?;
+    writeln!(file)?;
+
+    let reference: Vec<char> = reference_aligned.chars().map(needle_gap).collect();
+    let query: Vec<char> = query_aligned.chars().map(needle_gap).collect();
+    let name_width = reference_id.len().max(query_id.len()).max(13);
+    let position_width = stats.length.to_string().len().max(6);
+    let mut reference_position = 0;
+    let mut query_position = 0;
+
+    for start in (0..reference.len()).step_by(width.max(1)) {
+        let end = (start + width.max(1)).min(reference.len());
+        let reference_line: String = reference[start..end].iter().collect();
+        let query_line: String = query[start..end].iter().collect();
+        let markup: String = reference[start..end]
+            .iter()
+            .zip(&query[start..end])
+            .map(|(r, q)| match (r, q) {
+                ('-', _) | (_, '-') => ' ',
+                _ if r.eq_ignore_ascii_case(q) => '|',
+                _ => '.',
+            })
+            .collect();
+
+        let (reference_from, reference_to) = line_range(&reference_line, &mut reference_position);
+        let (query_from, query_to) = line_range(&query_line, &mut query_position);
+
+        writeln!(
+            file,
+            "{:<nw$} {:>pw$} {} {:>pw$}",
+            reference_id,
+            reference_from,
+            reference_line,
+            reference_to,
+            nw = name_width,
+            pw = position_width
+        )?;
+        writeln!(file, "{:<nw$} {:>pw$} {}", "", "", markup, nw = name_width, pw = position_width)?;
+        writeln!(
+            file,
+            "{:<nw$} {:>pw$} {} {:>pw$}",
+            query_id,
+            query_from,
+            query_line,
+            query_to,
+            nw = name_width,
+            pw = position_width
+        )?;
+        writeln!(file)?;
+    }
+
+    writeln!(file)?;
+    writeln!(file, "#---------------------------------------")?;
+    writeln!(file, "#---------------------------------------")?;
+    Ok(())
+}
+
+// needle writes gaps as '-'
+fn needle_gap(c: char) -> char {
+    if c == ALIGNMENT_GAP {
+        '-'
+    } else {
+        c
+    }
+}
+
+// 1-based positions of the first and last residue on a line. A line of gaps shows the
+// last position reached so far on both sides, as needle does.
+fn line_range(line: &str, position: &mut usize) -> (usize, usize) {
+    let residues = line.chars().filter(|c| *c != '-').count();
+    if residues == 0 {
+        return (*position, *position);
+    }
+    let from = *position + 1;
+    *position += residues;
+    (from, *position)
+}
@@ -0,0 +1,269 @@
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+use std::error::Error;
+use std::io::Write;
+
+use crate::formats::{AlignmentInput, AlignmentSpan, CigarOp};
+use crate::utils::create_output;
+
+// Longest QNAME the SAM specification allows; BAM stores its length plus NUL in one byte
+const MAX_QUERY_NAME_LENGTH: usize = 254;
+
+// Largest amount of data put into one BGZF block (as in htslib)
+const BGZF_BLOCK_SIZE: usize = 0xff00;
+
+// Empty BGZF block that marks the end of a BAM file
+const BGZF_EOF: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02, 0x00,
+    0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+// Fields of the single SAM/BAM record describing the query's alignment to the reference
+struct SamRecord<'a> {
+    query_name: &'a str,
+    flag: u16,
+    position: usize, // 0-based, converted to 1-based for SAM text
+    mapq: u8,
+    span: AlignmentSpan,
+    sequence: String,
+    score: i64,
+}
+
+impl<'a> SamRecord<'a> {
+    fn new(input: &'a AlignmentInput, span: AlignmentSpan, score: f64) -> SamRecord<'a> {
+        SamRecord {
+            query_name: input.query_id(),
+            flag: if span.is_mapped() { 0 } else { 0x4 },
+            position: span.reference_start,
+            mapq: span.mapq(),
+            span,
+            sequence: input.query_sequence.to_ascii_uppercase(),
+            score: score.round() as i64,
+        }
+    }
+}
+
+// Write the alignment as SAM text, or as BAM (binary SAM in BGZF blocks) when `bam` is set.
+// The header has one @SQ line for the reference record.
+pub fn write_sam(
+    output_file: &str,
+    input: &AlignmentInput,
+    reference_aligned: &str,
+    query_aligned: &str,
+    score: f64,
+    bam: bool,
+) -> Result<(), Box<dyn Error>> {
+    let span = AlignmentSpan::from_alignment(reference_aligned, query_aligned, input.query_sequence.chars().count());
+    let record = SamRecord::new(input, span, score);
+    if record.query_name.len() > MAX_QUERY_NAME_LENGTH {
+        return Err(format!(
+            "Query id '{}' is {} bytes long; SAM and BAM allow at most {}.",
+            record.query_name,
+            record.query_name.len(),
+            MAX_QUERY_NAME_LENGTH
+        )
+        .into());
+    }
+    let header = sam_header(input);
+
+    let mut file = create_output(output_file)?;
+    if bam {
+        let mut data = Vec::new();
+        encode_bam_header(&mut data, &header, input);
+        encode_bam_record(&mut data, &record);
+        write_bgzf(&mut file, &data)?;
+    } else {
+        file.write_all(header.as_bytes())?;
+        writeln!(file, "{}", sam_line(&record, input))?;
+    }
+    Ok(())
+}
+
+fn sam_header(input: &AlignmentInput) -> String {
+    format!(
+        "@HD\tVN:1.6\tSO:unsorted\n@SQ\tSN:{}\tLN:{}\n@PG\tID:aligner\tPN:aligner\tVN:{}\n",
+        input.reference_id(),
+        input.reference_sequence.chars().count(),
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+fn sam_line(record: &SamRecord, input: &AlignmentInput) -> String {
+    let mapped = record.span.is_mapped();
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t*\t0\t0\t{}\t*\tAS:i:{}\tNM:i:{}\tMD:Z:{}",
+        record.query_name,
+        record.flag,
+        if mapped { input.reference_id() } else { "*" },
+        if mapped { record.position + 1 } else { 0 },
+        record.mapq,
+        if mapped { record.span.cigar_string() } else { "*".to_string() },
+        record.sequence,
+        record.score,
+        record.span.edit_distance(),
+        record.span.md
+    )
+}
+
+fn encode_bam_header(data: &mut Vec<u8>, header: &str, input: &AlignmentInput) {
+    data.extend_from_slice(b"BAM\x01");
+    data.extend_from_slice(&(header.len() as i32).to_le_bytes());
+    data.extend_from_slice(header.as_bytes());
+    // One reference sequence
+    let name = input.reference_id();
+    data.extend_from_slice(&1i32.to_le_bytes());
+    data.extend_from_slice(&(name.len() as i32 + 1).to_le_bytes());
+    data.extend_from_slice(name.as_bytes());
+    data.push(0);
+    data.extend_from_slice(&(input.reference_sequence.chars().count() as i32).to_le_bytes());
+}
+
+fn encode_bam_record(data: &mut Vec<u8>, record: &SamRecord) {
+    let mapped = record.span.is_mapped();
+    let cigar: Vec<u32> = if mapped {
+        record
+            .span
+            .cigar
+            .iter()
+            .map(|(op, n)| (*n as u32) << 4 | bam_cigar_code(*op))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    // Unmapped records go in bin 4680 (reg2bin(-1, 0))
+    let bin = if mapped { reg2bin(record.position, record.span.reference_end) } else { 4680 };
+
+    let mut body = Vec::new();
+    body.extend_from_slice(&(if mapped { 0i32 } else { -1 }).to_le_bytes()); // refID
+    body.extend_from_slice(&(if mapped { record.position as i32 } else { -1 }).to_le_bytes()); // pos
+    body.push(record.query_name.len() as u8 + 1); // l_read_name
+    body.push(record.mapq);
+    body.extend_from_slice(&bin.to_le_bytes());
+    body.extend_from_slice(&(cigar.len() as u16).to_le_bytes());
+    body.extend_from_slice(&record.flag.to_le_bytes());
+    body.extend_from_slice(&(record.sequence.len() as i32).to_le_bytes());
+    body.extend_from_slice(&(-1i32).to_le_bytes()); // next refID
+    body.extend_from_slice(&(-1i32).to_le_bytes()); // next pos
+    body.extend_from_slice(&0i32.to_le_bytes()); // tlen
+    body.extend_from_slice(record.query_name.as_bytes());
+    body.push(0);
+    for op in &cigar {
+        body.extend_from_slice(&op.to_le_bytes());
+    }
+    // Sequence packed two bases per byte
+    let bases: Vec<u8> = record.sequence.bytes().map(bam_base_code).collect();
+    for pair in bases.chunks(2) {
+        body.push(pair[0] << 4 | pair.get(1).copied().unwrap_or(0));
+    }
+    // No base qualities
+    body.extend(std::iter::repeat_n(0xff, record.sequence.len()));
+    // Tags
+    body.extend_from_slice(b"ASi");
+    body.extend_from_slice(&(record.score as i32).to_le_bytes());
+    body.extend_from_slice(b"NMi");
+    body.extend_from_slice(&(record.span.edit_distance() as i32).to_le_bytes());
+    body.extend_from_slice(b"MDZ");
+    body.extend_from_slice(record.span.md.as_bytes());
+    body.push(0);
+
+    data.extend_from_slice(&(body.len() as i32).to_le_bytes());
+    data.extend_from_slice(&body);
+}
+
+fn bam_cigar_code(op: CigarOp) -> u32 {
+    match op {
+        CigarOp::Insertion => 1,
+        CigarOp::Deletion => 2,
+        CigarOp::SoftClip => 4,
+        CigarOp::Equal => 7,
+        CigarOp::Diff => 8,
+    }
+}
+
+// 4-bit base codes of "=ACMGRSVTWYHKDBN"; RNA 'U' is stored as 'T'
+fn bam_base_code(base: u8) -> u8 {
+    match base.to_ascii_uppercase() {
+        b'=' => 0,
+        b'A' => 1,
+        b'C' => 2,
+        b'M' => 3,
+        b'G' => 4,
+        b'R' => 5,
+        b'S' => 6,
+        b'V' => 7,
+        b'T' | b'U' => 8,
+        b'W' => 9,
+        b'Y' => 10,
+        b'H' => 11,
+        b'K' => 12,
+        b'D' => 13,
+        b'B' => 14,
+        _ => 15,
+    }
+}
+
+// UCSC binning scheme bin for the 0-based half-open interval [beg, end) (SAM spec 5.3)
+fn reg2bin(beg: usize, end: usize) -> u16 {
+    let end = end - 1;
+    for (shift, offset) in [(14, 4681), (17, 585), (20, 73), (23, 9), (26, 1)] {
+        if beg >> shift == end >> shift {
+            return (offset + (beg >> shift)) as u16;
+        }
+    }
+    0
+}
+
+// Compress data into BGZF blocks: gzip members with a BC extra field giving the block size
+fn write_bgzf(output: &mut dyn Write, data: &[u8]) -> Result<(), Box<dyn Error>> {
+    for chunk in data.chunks(BGZF_BLOCK_SIZE) {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(chunk)?;
+        let compressed = encoder.finish()?;
+        let mut crc = Crc::new();
+        crc.update(chunk);
+
+        // 18 byte header + compressed data + 8 byte footer, stored minus one
+        let block_size = (18 + compressed.len() + 8 - 1) as u16;
+        output.write_all(&[0x1f, 0x8b, 0x08, 0x04, 0, 0, 0, 0, 0, 0xff, 0x06, 0x00, b'B', b'C', 0x02, 0x00])?;
+        output.write_all(&block_size.to_le_bytes())?;
+        output.write_all(&compressed)?;
+        output.write_all(&crc.sum().to_le_bytes())?;
+        output.write_all(&(chunk.len() as u32).to_le_bytes())?;
+    }
+    output.write_all(&BGZF_EOF)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write_with_query_id(query_id: &str, output_file: &str) -> Result<(), Box<dyn Error>> {
+        let query_header = format!(">{}", query_id);
+        let input = AlignmentInput {
+            reference_header: ">reference",
+            reference_sequence: "ACGTACGT",
+            query_header: &query_header,
+            query_sequence: "ACGAACGT",
+        };
+        write_sam(output_file, &input, "ACGTACGT", "ACGAACGT", 4.0, false)
+    }
+
+    #[test]
+    fn query_ids_up_to_254_bytes_are_written() {
+        let output_file = std::env::temp_dir().join(format!("aligner_sam_test_{}.sam", std::process::id()));
+        let output_file = output_file.to_str().unwrap();
+
+        let error = write_with_query_id(&"q".repeat(255), output_file).unwrap_err();
+        assert!(error.to_string().contains("255 bytes long"), "{}", error);
+
+        write_with_query_id(&"q".repeat(254), output_file).unwrap();
+        let sam = fs::read_to_string(output_file).unwrap();
+        fs::remove_file(output_file).unwrap();
+        let record: Vec<&str> = sam.lines().last().unwrap().split('\t').collect();
+        assert_eq!(record[0].len(), 254);
+        assert_eq!(record[5], "3=1X4=");
+        assert_eq!(&record[12..], ["NM:i:1", "MD:Z:3T4"]);
+    }
+}
@@ -0,0 +1,112 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+// Numeric type usable as an alignment score (i16, i32, i64 or f64 for log-odds matrices).
+// All arithmetic done by the aligner goes through the checked operations below, so a
+// score that no longer fits in the type is reported instead of silently wrapping.
+pub trait Score: Copy + PartialOrd + fmt::Display + fmt::Debug + FromStr {
+    // Name used on the command line and in error messages
+    const NAME: &'static str;
+
+    fn zero() -> Self;
+
+    // Addition, None on overflow
+    fn try_add(self, other: Self) -> Option<Self>;
+
+    // Value as f64, for output formats that need a float or a rounded integer
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! impl_integer_score {
+    ($($t:ty),*) => {
+        $(
+            impl Score for $t {
+                const NAME: &'static str = stringify!($t);
+
+                fn zero() -> Self {
+                    0
+                }
+
+                fn try_add(self, other: Self) -> Option<Self> {
+                    self.checked_add(other)
+                }
+
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_score!(i16, i32, i64);
+
+// Floating point scores "overflow" when they stop being finite
+impl Score for f64 {
+    const NAME: &'static str = "f64";
+
+    fn zero() -> Self {
+        0.0
+    }
+
+    fn try_add(self, other: Self) -> Option<Self> {
+        let sum = self + other;
+        if sum.is_finite() {
+            Some(sum)
+        } else {
+            None
+        }
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+// Error returned when a score leaves the range of the score type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScoreOverflow {
+    pub score_type: &'static str,
+}
+
+impl fmt::Display for ScoreOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Alignment score overflowed the {} score type.",
+            self.score_type
+        )
+    }
+}
+
+impl Error for ScoreOverflow {}
+
+pub fn add<S: Score>(a: S, b: S) -> Result<S, ScoreOverflow> {
+    a.try_add(b).ok_or(ScoreOverflow { score_type: S::NAME })
+}
+
+// Larger of two scores (scores are never NaN, so PartialOrd is enough)
+pub fn max<S: Score>(a: S, b: S) -> S {
+    if b > a {
+        b
+    } else {
+        a
+    }
+}
+
+// Parse a score given on the command line, e.g. "-2" or "-1.5" for f64
+pub fn parse_score<S: Score>(value: &str, name: &str) -> Result<S, Box<dyn Error>> {
+    let parsed = value.trim().parse::<S>().ok();
+    // Reject NaN and infinities for f64
+    match parsed.filter(|score| score.try_add(S::zero()).is_some()) {
+        Some(score) => Ok(score),
+        None => Err(format!(
+            "Invalid {} '{}' for score type {}.",
+            name,
+            value,
+            S::NAME
+        )
+        .into()),
+    }
+}
@@ -1,66 +1,445 @@
-use std::collections::HashMap;
+use flate2::read::MultiGzDecoder;
 use std::error::Error;
 use std::fs;
 use std::io::{self, BufRead, Write};
 
-// Translate DNA/RNA sequence to amino acid sequence
-pub fn translate_sequence(dna_sequence: &str) -> Result<String, Box<dyn Error>> {
-    let codon_table = get_codon_table();
+use crate::genetic_code::GeneticCode;
+use crate::modified_bases::{ModifiedBaseAlphabet, ModifiedBaseCounts};
 
-    let mut aa_sequence = String::new();
+// What to do at a stop codon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopCodons {
+    // End the protein before the first stop codon
+    StopAtFirst,
+    // Translate every stop codon as '*' and carry on
+    TranslateThrough,
+}
+
+// What to do with one or two bases left over after the last complete codon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartialCodon {
+    Drop,
+    // Pad with N and translate, which gives an amino acid only when the missing bases
+    // do not matter (GC -> GCN -> A) and 'X' otherwise
+    Resolve,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TranslationOptions<'a> {
+    pub stop_codons: StopCodons,
+    pub partial_codon: PartialCodon,
+    // Modified bases to read as their canonical base. Not needed for sequences from
+    // open_fasta, which are canonical already.
+    pub modified_bases: Option<&'a ModifiedBaseAlphabet>,
+}
+
+// Translate a DNA/RNA sequence in frame from its first base. Codons with IUPAC ambiguity
+// codes translate to the amino acid all their expansions agree on, or 'X'.
+pub fn translate(
+    sequence: &str,
+    genetic_code: &GeneticCode,
+    options: TranslationOptions,
+) -> Result<String, Box<dyn Error>> {
+    let canonical;
+    let sequence = match options.modified_bases {
+        Some(alphabet) => {
+            canonical = alphabet.canonicalize(sequence).0;
+            &canonical
+        }
+        None => sequence,
+    };
+    // Bases are characters, so a multi-byte symbol does not shift the frame
+    let bases: Vec<char> = sequence.chars().collect();
+    let mut protein = String::with_capacity(bases.len() / 3 + 1);
+    let codons = bases.chunks_exact(3);
+    let remainder = codons.remainder();
+    for codon in codons {
+        let amino_acid = genetic_code.translate_ambiguous(&codon.iter().collect::<String>());
+        if amino_acid == '*' && options.stop_codons == StopCodons::StopAtFirst {
+            return Ok(protein);
+        }
+        protein.push(amino_acid);
+    }
+
+    if !remainder.is_empty() {
+        match options.partial_codon {
+            PartialCodon::Drop => {}
+            PartialCodon::Resolve => {
+                let mut codon: String = remainder.iter().collect();
+                for _ in remainder.len()..3 {
+                    codon.push('N');
+                }
+                let amino_acid = genetic_code.translate_ambiguous(&codon);
+                if amino_acid != '*' || options.stop_codons == StopCodons::TranslateThrough {
+                    protein.push(amino_acid);
+                }
+            }
+            PartialCodon::Error => {
+                return Err(format!(
+                    "Sequence length {} is not a multiple of 3: {} trailing base(s) after the last codon.",
+                    bases.len(),
+                    remainder.len()
+                )
+                .into());
+            }
+        }
+    }
+    Ok(protein)
+}
+
+// Translate DNA/RNA sequence to amino acid sequence with the given genetic code, from the
+// first start codon of the code up to the first stop codon. The start codon is read as
+// Met even when it is an alternative start such as GTG.
+pub fn translate_sequence(dna_sequence: &str, genetic_code: &GeneticCode) -> Result<String, Box<dyn Error>> {
 
     // Convert to uppercase for fun (already should be upppercase) and replace 'U' with 'T' to handle RNA sequences in a way that aligns with the existing codon table
     let dna_sequence = dna_sequence.to_uppercase().replace('U', "T");
 
-    let start_index = dna_sequence.find("ATG");
+    // Look for the start codon at every base, by characters so that multi-byte bases are skipped whole
+    let bases: Vec<char> = dna_sequence.chars().collect();
+    let start_index = bases
+        .windows(3)
+        .position(|codon| genetic_code.is_start(&codon.iter().collect::<String>()));
+
+    let start_index = match start_index {
+        Some(start_index) => start_index,
+        None => {
+            return Err(format!(
+                "No start codon ({}) found in the provided sequence: '{}'. Length: {}",
+                genetic_code.start_codons().join(", "),
+                dna_sequence,
+                bases.len()
+            )
+            .into())
+        }
+    };
 
-    // Check if a start codon 'ATG' is found
-    if start_index.is_none() {
-        return Err(format!(
-            "Start codon 'ATG' not found in the provided sequence: '{}'. Length: {}",
-            dna_sequence,
-            dna_sequence.len()
-        )
-        .into());
+    let options = TranslationOptions {
+        stop_codons: StopCodons::StopAtFirst,
+        partial_codon: PartialCodon::Drop,
+        modified_bases: None,
+    };
+    let coding: String = bases[start_index..].iter().collect();
+    let protein = translate(&coding, genetic_code, options)?;
+    // The initiator tRNA reads any start codon as Met
+    Ok(format!("M{}", protein.chars().skip(1).collect::<String>()))
+}
+
+// Translate a coding sequence in frame from its first base, up to and including the first
+// stop codon ('*'). An incomplete last codon is ignored.
+pub fn translate_in_frame(coding_sequence: &str, genetic_code: &GeneticCode) -> String {
+    let options = TranslationOptions {
+        stop_codons: StopCodons::TranslateThrough,
+        partial_codon: PartialCodon::Drop,
+        modified_bases: None,
+    };
+    // Dropping the partial codon cannot fail
+    let mut protein = translate(coding_sequence, genetic_code, options).unwrap_or_default();
+    if let Some(stop) = protein.find('*') {
+        protein.truncate(stop + 1);
     }
+    protein
+}
 
-    let mut i = start_index.unwrap(); // From ATG
+// One record of a FASTA (or FASTQ) file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FastaRecord {
+    pub id: String,
+    pub description: String,
+    pub sequence: String,
+    // Per-base quality string, only for records read from FASTQ
+    pub quality: Option<String>,
+    // Modified bases replaced by their canonical base in `sequence`
+    pub modified_bases: ModifiedBaseCounts,
+}
 
-    // Loop through the sequence in steps of codon size until the end
-    while i + 3 <= dna_sequence.len() {
-        let codon = &dna_sequence[i..i + 3]; // Extract a codon
-        let amino_acid = codon_table.get(codon).unwrap_or(&"X"); // Look up amino acid for the codon. X should never happen.
-        
-        // Stop for stop codon
-        if *amino_acid == "*" {
-            break;
+impl FastaRecord {
+    // Header line as written in the file, including the leading '>'
+    pub fn header(&self) -> String {
+        if self.description.is_empty() {
+            format!(">{}", self.id)
+        } else {
+            format!(">{} {}", self.id, self.description)
         }
-        
-        aa_sequence.push_str(amino_acid);
-        i += 3; // Move to the next codon
     }
+}
 
-    Ok(aa_sequence)
+// Streams records out of FASTA text one at a time
+pub struct FastaReader<R: BufRead> {
+    lines: io::Lines<R>,
+    line_number: usize,
+    // Header of the record being read, already consumed from `lines`
+    pending_header: Option<(String, usize)>,
+    alphabet: ModifiedBaseAlphabet,
 }
 
-// Read a sequence from a FASTA file
-pub fn read_fasta_sequence(file_path: &str) -> Result<(String, String), Box<dyn Error>> {
-    let file = fs::File::open(file_path)?;
-    let reader = io::BufReader::new(file);
+impl<R: BufRead> FastaReader<R> {
+    pub fn new(reader: R, alphabet: ModifiedBaseAlphabet) -> Self {
+        FastaReader {
+            lines: reader.lines(),
+            line_number: 0,
+            pending_header: None,
+            alphabet,
+        }
+    }
+
+    fn read_record(&mut self) -> Result<Option<FastaRecord>, Box<dyn Error>> {
+        // Find the header, skipping blank lines before it
+        let (header_line, header_number) = match self.pending_header.take() {
+            Some(header) => header,
+            None => loop {
+                match self.lines.next() {
+                    None => return Ok(None),
+                    Some(line) => {
+                        let line = line?;
+                        self.line_number += 1;
+                        if line.starts_with('>') {
+                            break (line, self.line_number);
+                        } else if !line.trim().is_empty() {
+                            return Err(format!(
+                                "Line {}: sequence data before the first FASTA header.",
+                                self.line_number
+                            )
+                            .into());
+                        }
+                    }
+                }
+            },
+        };
+        let (id, description) = parse_header(&header_line, header_number)?;
 
-    let mut header = String::new();
-    let mut sequence = String::new();
+        // Sequence lines up to the next header
+        let mut sequence = String::new();
+        for line in self.lines.by_ref() {
+            let line = line?;
+            self.line_number += 1;
+            if line.starts_with('>') {
+                self.pending_header = Some((line, self.line_number));
+                break;
+            }
+            for c in line.chars().filter(|c| !c.is_whitespace()) {
+                if !is_sequence_char(c) && !self.alphabet.contains(c) {
+                    return Err(format!(
+                        "Line {}: invalid character '{}' in sequence of record '{}'.",
+                        self.line_number, c, id
+                    )
+                    .into());
+                }
+                sequence.push(c);
+            }
+        }
 
-    for line in reader.lines() {
-        let line = line?;
-        if line.starts_with('>') {
-            header = line;
-        } else {
-            sequence.push_str(&line.trim());
+        if sequence.is_empty() {
+            return Err(format!("Line {}: record '{}' has no sequence.", header_number, id).into());
         }
+
+        let (sequence, modified_bases) = self.alphabet.canonicalize(&sequence);
+        Ok(Some(FastaRecord { id, description, sequence, quality: None, modified_bases }))
+    }
+}
+
+impl<R: BufRead> Iterator for FastaReader<R> {
+    type Item = Result<FastaRecord, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
     }
+}
 
-    Ok((header, sequence))
+// Streams records out of FASTQ text (four lines per record: @header, sequence, +, qualities)
+pub struct FastqReader<R: BufRead> {
+    lines: io::Lines<R>,
+    line_number: usize,
+    alphabet: ModifiedBaseAlphabet,
+}
+
+impl<R: BufRead> FastqReader<R> {
+    pub fn new(reader: R, alphabet: ModifiedBaseAlphabet) -> Self {
+        FastqReader {
+            lines: reader.lines(),
+            line_number: 0,
+            alphabet,
+        }
+    }
+
+    fn next_line(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        match self.lines.next() {
+            None => Ok(None),
+            Some(line) => {
+                self.line_number += 1;
+                Ok(Some(line?))
+            }
+        }
+    }
+
+    fn read_record(&mut self) -> Result<Option<FastaRecord>, Box<dyn Error>> {
+        // Skip blank lines between records
+        let header_line = loop {
+            match self.next_line()? {
+                None => return Ok(None),
+                Some(line) if line.trim().is_empty() => continue,
+                Some(line) => break line,
+            }
+        };
+        let header_number = self.line_number;
+        if !header_line.starts_with('@') {
+            return Err(format!("Line {}: FASTQ record does not start with '@'.", header_number).into());
+        }
+        let (id, description) = parse_header(&header_line, header_number)?;
+
+        let truncated = || format!("Line {}: FASTQ record '{}' is truncated.", header_number, id);
+        let sequence = self.next_line()?.ok_or_else(truncated)?.trim().to_string();
+        let separator = self.next_line()?.ok_or_else(truncated)?;
+        if !separator.starts_with('+') {
+            return Err(format!("Line {}: expected '+' separator in FASTQ record '{}'.", self.line_number, id).into());
+        }
+        let quality = self.next_line()?.ok_or_else(truncated)?.trim().to_string();
+
+        if let Some(c) = sequence.chars().find(|c| !is_sequence_char(*c) && !self.alphabet.contains(*c)) {
+            return Err(format!("Line {}: invalid character '{}' in sequence of record '{}'.", header_number + 1, c, id).into());
+        }
+        let bases = sequence.chars().count();
+        if quality.len() != bases {
+            return Err(format!(
+                "Line {}: record '{}' has {} quality values for {} bases.",
+                self.line_number,
+                id,
+                quality.len(),
+                bases
+            )
+            .into());
+        }
+
+        let (sequence, modified_bases) = self.alphabet.canonicalize(&sequence);
+        Ok(Some(FastaRecord { id, description, sequence, quality: Some(quality), modified_bases }))
+    }
+}
+
+impl<R: BufRead> Iterator for FastqReader<R> {
+    type Item = Result<FastaRecord, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+// Split "id description" out of a FASTA '>' or FASTQ '@' header line
+fn parse_header(line: &str, line_number: usize) -> Result<(String, String), Box<dyn Error>> {
+    let header = line[1..].trim();
+    let mut parts = header.splitn(2, char::is_whitespace);
+    let id = parts.next().unwrap_or("");
+    if id.is_empty() {
+        return Err(format!("Line {}: header without a sequence id.", line_number).into());
+    }
+    let description = parts.next().unwrap_or("").trim();
+    Ok((id.to_string(), description.to_string()))
+}
+
+// Residue letters, stop '*' and the gap characters used in aligned FASTA
+fn is_sequence_char(c: char) -> bool {
+    c.is_ascii_alphabetic() || matches!(c, '*' | '-' | '.' | '_')
+}
+
+// File name that stands for stdin (inputs) or stdout (outputs)
+pub const STDIO_PATH: &str = "-";
+
+// Open a file (or stdin for "-") for buffered reading, decompressing it on the fly if it starts
+// with the gzip magic bytes. BGZF files are a series of gzip members, which MultiGzDecoder reads in turn.
+pub fn open_maybe_gzipped(file_path: &str) -> Result<Box<dyn BufRead>, Box<dyn Error>> {
+    let mut reader: Box<dyn BufRead> = if file_path == STDIO_PATH {
+        Box::new(io::BufReader::new(io::stdin()))
+    } else {
+        let file = fs::File::open(file_path)
+            .map_err(|e| format!("Cannot open file '{}': {}", file_path, e))?;
+        Box::new(io::BufReader::new(file))
+    };
+    let is_gzip = reader.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+    if is_gzip {
+        Ok(Box::new(io::BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+// Lazily yields the records of a FASTA or FASTQ stream
+pub type RecordStream = Box<dyn Iterator<Item = Result<FastaRecord, Box<dyn Error>>>>;
+
+// Open a FASTA or FASTQ file (or stdin for "-"), optionally gzip/BGZF compressed, as a record stream.
+// The format is taken from the first non-blank character ('>' FASTA, '@' FASTQ). Modified bases
+// of the alphabet are replaced by their canonical base.
+pub fn open_fasta(file_path: &str, alphabet: &ModifiedBaseAlphabet) -> Result<RecordStream, Box<dyn Error>> {
+    let mut reader = open_maybe_gzipped(file_path)?;
+    let is_fastq = loop {
+        let buffer = reader.fill_buf()?;
+        match buffer.iter().position(|b| !b.is_ascii_whitespace()) {
+            Some(pos) => break buffer[pos] == b'@',
+            None if buffer.is_empty() => break false,
+            None => {
+                let consumed = buffer.len();
+                reader.consume(consumed);
+            }
+        }
+    };
+    if is_fastq {
+        Ok(Box::new(FastqReader::new(reader, alphabet.clone())))
+    } else {
+        Ok(Box::new(FastaReader::new(reader, alphabet.clone())))
+    }
+}
+
+// Read every record of a (possibly aligned) multi-record FASTA or FASTQ file
+pub fn read_fasta_records(file_path: &str, alphabet: &ModifiedBaseAlphabet) -> Result<Vec<FastaRecord>, Box<dyn Error>> {
+    open_fasta(file_path, alphabet)?
+        .map(|record| record.map_err(|e| format!("'{}': {}", file_path, e).into()))
+        .collect()
+}
+
+// Pick one record of a FASTA/FASTQ file by id, or by 1-based index. Defaults to the first record.
+pub fn select_fasta_record(
+    file_path: &str,
+    id: Option<&str>,
+    index: Option<usize>,
+    alphabet: &ModifiedBaseAlphabet,
+) -> Result<FastaRecord, Box<dyn Error>> {
+    if index == Some(0) {
+        return Err("FASTA record indices start at 1.".into());
+    }
+    for (i, record) in open_fasta(file_path, alphabet)?.enumerate() {
+        let record = record.map_err(|e| format!("'{}': {}", file_path, e))?;
+        let selected = match (id, index) {
+            (Some(id), _) => record.id == id,
+            (None, Some(index)) => i + 1 == index,
+            (None, None) => true,
+        };
+        if selected {
+            return Ok(record);
+        }
+    }
+
+    Err(match (id, index) {
+        (Some(id), _) => format!("No record with id '{}' in '{}'.", id, file_path),
+        (None, Some(index)) => format!("'{}' has fewer than {} records.", file_path, index),
+        (None, None) => format!("No FASTA records in '{}'.", file_path),
+    }
+    .into())
+}
+
+// Read the first sequence of a FASTA file as (header line, sequence)
+pub fn read_fasta_sequence(file_path: &str, alphabet: &ModifiedBaseAlphabet) -> Result<(String, String), Box<dyn Error>> {
+    let record = select_fasta_record(file_path, None, None, alphabet)?;
+    Ok((record.header(), record.sequence))
+}
+
+// Create an output file, or write to stdout for "-"
+pub fn create_output(file_path: &str) -> Result<Box<dyn Write>, Box<dyn Error>> {
+    if file_path == STDIO_PATH {
+        Ok(Box::new(io::stdout()))
+    } else {
+        let file = fs::File::create(file_path)
+            .map_err(|e| format!("Cannot create output file '{}': {}", file_path, e))?;
+        Ok(Box::new(file))
+    }
 }
 
 pub fn save_sequence_to_file(
@@ -68,77 +447,36 @@ pub fn save_sequence_to_file(
     header: &str,
     sequence: &str,
 ) -> Result<(), Box<dyn Error>> {
-    let mut file = fs::File::create(file_path)?;
+    let mut file = create_output(file_path)?;
     writeln!(file, "{}", header)?;
     writeln!(file, "{}", sequence)?;
     Ok(())
 }
 
-fn get_codon_table() -> HashMap<&'static str, &'static str> {
-    let mut codon_table = HashMap::new();
-    codon_table.insert("TTT", "F");
-    codon_table.insert("TTC", "F");
-    codon_table.insert("TTA", "L");
-    codon_table.insert("TTG", "L");
-    codon_table.insert("CTT", "L");
-    codon_table.insert("CTC", "L");
-    codon_table.insert("CTA", "L");
-    codon_table.insert("CTG", "L");
-    codon_table.insert("ATT", "I");
-    codon_table.insert("ATC", "I");
-    codon_table.insert("ATA", "I");
-    codon_table.insert("ATG", "M"); // Start
-    codon_table.insert("GTT", "V");
-    codon_table.insert("GTC", "V");
-    codon_table.insert("GTA", "V");
-    codon_table.insert("GTG", "V");
-    codon_table.insert("TCT", "S");
-    codon_table.insert("TCC", "S");
-    codon_table.insert("TCA", "S");
-    codon_table.insert("TCG", "S");
-    codon_table.insert("CCT", "P");
-    codon_table.insert("CCC", "P");
-    codon_table.insert("CCA", "P");
-    codon_table.insert("CCG", "P");
-    codon_table.insert("ACT", "T");
-    codon_table.insert("ACC", "T");
-    codon_table.insert("ACA", "T");
-    codon_table.insert("ACG", "T");
-    codon_table.insert("GCT", "A");
-    codon_table.insert("GCC", "A");
-    codon_table.insert("GCA", "A");
-    codon_table.insert("GCG", "A");
-    codon_table.insert("TAT", "Y");
-    codon_table.insert("TAC", "Y");
-    codon_table.insert("TAA", "*");
-    codon_table.insert("TAG", "*");
-    codon_table.insert("CAT", "H");
-    codon_table.insert("CAC", "H");
-    codon_table.insert("CAA", "Q");
-    codon_table.insert("CAG", "Q");
-    codon_table.insert("AAT", "N");
-    codon_table.insert("AAC", "N");
-    codon_table.insert("AAA", "K");
-    codon_table.insert("AAG", "K");
-    codon_table.insert("GAT", "D");
-    codon_table.insert("GAC", "D");
-    codon_table.insert("GAA", "E");
-    codon_table.insert("GAG", "E");
-    codon_table.insert("TGT", "C");
-    codon_table.insert("TGC", "C");
-    codon_table.insert("TGA", "*");
-    codon_table.insert("TGG", "W");
-    codon_table.insert("CGT", "R");
-    codon_table.insert("CGC", "R");
-    codon_table.insert("CGA", "R");
-    codon_table.insert("CGG", "R");
-    codon_table.insert("AGT", "S");
-    codon_table.insert("AGC", "S");
-    codon_table.insert("AGA", "R");
-    codon_table.insert("AGG", "R");
-    codon_table.insert("GGT", "G");
-    codon_table.insert("GGC", "G");
-    codon_table.insert("GGA", "G");
-    codon_table.insert("GGG", "G");
-    codon_table
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genetic_code::{genetic_code, standard_code};
+
+    #[test]
+    fn translate_sequence_starts_at_an_alternative_start_codon() {
+        let bacterial = genetic_code(11).unwrap();
+        assert_eq!(translate_sequence("GTGAAACCCTAA", bacterial).unwrap(), "MKP");
+        // GTG comes before the ATG and sets the frame
+        assert_eq!(translate_sequence("CCGTGAAAATGCCCTAA", bacterial).unwrap(), "MKMP");
+        assert!(translate_sequence("CCCGGGTAA", bacterial).is_err());
+    }
+
+    #[test]
+    fn translate_counts_a_multi_byte_base_as_one_base() {
+        let options = TranslationOptions {
+            stop_codons: StopCodons::StopAtFirst,
+            partial_codon: PartialCodon::Error,
+            modified_bases: None,
+        };
+        // Ψ is two bytes but one base, so the codons are ATG ΨAA GCA
+        assert_eq!(translate("ATGΨAAGCA", standard_code(), options).unwrap(), "MXA");
+        let error = translate("ATGΨAAGC", standard_code(), options).unwrap_err();
+        assert!(error.to_string().contains("2 trailing base(s)"), "{}", error);
+    }
 }
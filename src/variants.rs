@@ -0,0 +1,168 @@
+// Differences between a pairwise alignment's query and reference, in reference coordinates
+use crate::formats::ALIGNMENT_GAP;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariantKind {
+    Snv,       // One substituted residue
+    Mnv,       // Several adjacent substituted residues
+    Insertion, // Query residues between two reference residues
+    Deletion,  // Reference residues missing from the query
+}
+
+impl VariantKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            VariantKind::Snv => "SNV",
+            VariantKind::Mnv => "MNV",
+            VariantKind::Insertion => "INS",
+            VariantKind::Deletion => "DEL",
+        }
+    }
+}
+
+// One variant without anchor bases. `start` is the 0-based reference position of the
+// first changed residue; an insertion goes before the residue at `start`. `reference`
+// is empty for insertions and `alternate` is empty for deletions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    pub kind: VariantKind,
+    pub start: usize,
+    pub reference: String,
+    pub alternate: String,
+}
+
+impl Variant {
+    // Reference position after the last changed residue
+    pub fn end(&self) -> usize {
+        self.start + self.reference.chars().count()
+    }
+}
+
+// Walk the aligned strings ('_' gaps) and collect runs of mismatches, insertions and
+// deletions. Only the aligned region is used: end gaps mean the other sequence does
+// not cover that part, not that residues were inserted or deleted.
+pub fn extract_variants(reference_aligned: &str, query_aligned: &str) -> Vec<Variant> {
+    let columns: Vec<(char, char)> = reference_aligned
+        .chars()
+        .zip(query_aligned.chars())
+        .map(|(r, q)| (r.to_ascii_uppercase(), q.to_ascii_uppercase()))
+        .collect();
+    let is_aligned = |(r, q): &(char, char)| *r != ALIGNMENT_GAP && *q != ALIGNMENT_GAP;
+    let (first, last) = match (columns.iter().position(is_aligned), columns.iter().rposition(is_aligned)) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Vec::new(),
+    };
+
+    let mut position = columns[..first].iter().filter(|(r, _)| *r != ALIGNMENT_GAP).count();
+    let mut variants: Vec<Variant> = Vec::new();
+    for &(r, q) in &columns[first..=last] {
+        let kind = if r == ALIGNMENT_GAP {
+            Some(VariantKind::Insertion)
+        } else if q == ALIGNMENT_GAP {
+            Some(VariantKind::Deletion)
+        } else if r != q {
+            Some(VariantKind::Snv)
+        } else {
+            None
+        };
+
+        if let Some(kind) = kind {
+            // Extend the previous variant if this column continues it
+            let extends = match variants.last() {
+                Some(previous) => match (previous.kind, kind) {
+                    (VariantKind::Insertion, VariantKind::Insertion) => previous.start == position,
+                    (VariantKind::Deletion, VariantKind::Deletion) => previous.end() == position,
+                    (VariantKind::Snv | VariantKind::Mnv, VariantKind::Snv) => previous.end() == position,
+                    _ => false,
+                },
+                None => false,
+            };
+            if extends {
+                let previous = variants.last_mut().unwrap();
+                if r != ALIGNMENT_GAP {
+                    previous.reference.push(r);
+                }
+                if q != ALIGNMENT_GAP {
+                    previous.alternate.push(q);
+                }
+                if previous.kind == VariantKind::Snv {
+                    previous.kind = VariantKind::Mnv;
+                }
+            } else {
+                variants.push(Variant {
+                    kind,
+                    start: position,
+                    reference: if r == ALIGNMENT_GAP { String::new() } else { r.to_string() },
+                    alternate: if q == ALIGNMENT_GAP { String::new() } else { q.to_string() },
+                });
+            }
+        }
+
+        if r != ALIGNMENT_GAP {
+            position += 1;
+        }
+    }
+    variants
+}
+
+// Shift an insertion or deletion as far right as the reference allows (the HGVS 3' rule)
+pub fn right_normalize(variant: &Variant, reference: &[char]) -> Variant {
+    let mut shifted = variant.clone();
+    match variant.kind {
+        VariantKind::Deletion => {
+            let mut deleted: Vec<char> = variant.reference.chars().collect();
+            while shifted.start + deleted.len() < reference.len()
+                && reference[shifted.start + deleted.len()].eq_ignore_ascii_case(&deleted[0])
+            {
+                let next = reference[shifted.start + deleted.len()].to_ascii_uppercase();
+                deleted.remove(0);
+                deleted.push(next);
+                shifted.start += 1;
+            }
+            shifted.reference = deleted.into_iter().collect();
+        }
+        VariantKind::Insertion => {
+            let mut inserted: Vec<char> = variant.alternate.chars().collect();
+            while shifted.start < reference.len() && reference[shifted.start].eq_ignore_ascii_case(&inserted[0]) {
+                let first = inserted.remove(0);
+                inserted.push(first);
+                shifted.start += 1;
+            }
+            shifted.alternate = inserted.into_iter().collect();
+        }
+        VariantKind::Snv | VariantKind::Mnv => {}
+    }
+    shifted
+}
+
+// Shift an insertion or deletion as far left as the reference allows (substitutions are
+// returned unchanged). `reference` is the ungapped reference sequence.
+pub fn left_normalize(variant: &Variant, reference: &[char]) -> Variant {
+    let mut shifted = variant.clone();
+    match variant.kind {
+        VariantKind::Deletion => {
+            let mut deleted: Vec<char> = variant.reference.chars().collect();
+            while shifted.start > 0
+                && reference[shifted.start - 1].eq_ignore_ascii_case(deleted.last().unwrap())
+            {
+                shifted.start -= 1;
+                deleted.pop();
+                deleted.insert(0, reference[shifted.start].to_ascii_uppercase());
+            }
+            shifted.reference = deleted.into_iter().collect();
+        }
+        VariantKind::Insertion => {
+            let mut inserted: Vec<char> = variant.alternate.chars().collect();
+            while shifted.start > 0
+                && reference[shifted.start - 1].eq_ignore_ascii_case(inserted.last().unwrap())
+            {
+                shifted.start -= 1;
+                let last = inserted.pop().unwrap();
+                inserted.insert(0, last);
+            }
+            shifted.alternate = inserted.into_iter().collect();
+        }
+        VariantKind::Snv | VariantKind::Mnv => {}
+    }
+    shifted
+}
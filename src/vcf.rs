@@ -0,0 +1,73 @@
+use std::error::Error;
+use std::io::Write;
+
+use crate::formats::AlignmentInput;
+use crate::utils::create_output;
+use crate::variants::{extract_variants, left_normalize, Variant, VariantKind};
+
+// Write the query's differences from the reference as VCF 4.3: one haploid sample named
+// after the query, the reference record as the only contig, indels left-normalised and
+// anchored on the preceding reference base
+pub fn write_vcf(
+    output_file: &str,
+    input: &AlignmentInput,
+    reference_aligned: &str,
+    query_aligned: &str,
+) -> Result<(), Box<dyn Error>> {
+    let reference: Vec<char> = input.reference_sequence.chars().collect();
+    let mut variants: Vec<Variant> = extract_variants(reference_aligned, query_aligned)
+        .iter()
+        .map(|variant| left_normalize(variant, &reference))
+        .collect();
+    variants.sort_by_key(|variant| variant.start);
+
+    let mut file = create_output(output_file)?;
+    writeln!(file, "##fileformat=VCFv4.3")?;
+    writeln!(file, "##source=aligner {}", env!("CARGO_PKG_VERSION"))?;
+    writeln!(file, "##contig=<ID={},length={}>", input.reference_id(), reference.len())?;
+    writeln!(
+        file,
+        "##INFO=<ID=TYPE,Number=1,Type=String,Description=\"Variant type: SNV, MNV, INS or DEL\">"
+    )?;
+    writeln!(file, "##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">")?;
+    writeln!(file, "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\t{}", input.query_id())?;
+
+    for variant in &variants {
+        let (position, reference_allele, alternate_allele) = vcf_alleles(variant, &reference);
+        writeln!(
+            file,
+            "{}\t{}\t.\t{}\t{}\t.\tPASS\tTYPE={}\tGT\t1",
+            input.reference_id(),
+            position,
+            reference_allele,
+            alternate_allele,
+            variant.kind.name()
+        )?;
+    }
+    Ok(())
+}
+
+// 1-based POS and REF/ALT alleles. Indels get the reference base before them as anchor,
+// or the base after them when they start at the first reference position.
+fn vcf_alleles(variant: &Variant, reference: &[char]) -> (usize, String, String) {
+    match variant.kind {
+        VariantKind::Snv | VariantKind::Mnv => (variant.start + 1, variant.reference.clone(), variant.alternate.clone()),
+        VariantKind::Insertion | VariantKind::Deletion => {
+            if variant.start > 0 {
+                let anchor = reference[variant.start - 1].to_ascii_uppercase();
+                (
+                    variant.start,
+                    format!("{}{}", anchor, variant.reference),
+                    format!("{}{}", anchor, variant.alternate),
+                )
+            } else {
+                let anchor = reference[variant.end()].to_ascii_uppercase();
+                (
+                    1,
+                    format!("{}{}", variant.reference, anchor),
+                    format!("{}{}", variant.alternate, anchor),
+                )
+            }
+        }
+    }
+}